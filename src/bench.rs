@@ -0,0 +1,307 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use colored::Colorize;
+use prost_reflect::MethodDescriptor;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::transport::Channel;
+use crate::body_template::BodyTemplate;
+use crate::descriptors;
+use crate::dynamic_codec::DynamicCodec;
+use crate::grpc_client;
+use crate::grpc_request_dsl::{ServiceRequest, Timeouts};
+use crate::grpc_status::{GrpcStatus, StatusCode};
+use crate::latency::{format_duration, LatencyStats};
+
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    pub total: Option<u64>,
+    pub duration: Option<Duration>,
+    pub rps: Option<u64>,
+    pub concurrency: usize,
+    pub connections: usize,
+}
+
+#[derive(Debug)]
+struct Sample {
+    latency: Duration,
+    code: StatusCode,
+}
+
+const DEFAULT_TOTAL: u64 = 200;
+
+pub fn run(service_request: &ServiceRequest, options: BenchOptions) -> Result<bool, String> {
+    if options.concurrency == 0 || options.connections == 0 {
+        return Err(String::from("Concurrency and connections must be greater than 0"));
+    }
+    let method = descriptors::method_of(service_request)?;
+    let template = BodyTemplate::from(&service_request.body.0)?;
+    // fail fast when the body can't be converted, rather than failing every request
+    grpc_client::encode_body(&method, &template.render(0))?;
+
+    let total = match (options.total, options.duration) {
+        (None, None) => Some(DEFAULT_TOTAL),
+        (total, _) => total,
+    };
+    println!("Running {} against {}{}",
+             service_request.service_function,
+             service_request.target(),
+             match (total, options.duration) {
+                 (Some(n), Some(d)) => format!(" for {} requests or {}", n, format_duration(d)),
+                 (Some(n), None) => format!(" for {} requests", n),
+                 (None, Some(d)) => format!(" for {}", format_duration(d)),
+                 (None, None) => String::new(),
+             });
+    println!("concurrency {}, connections {}, rps {}\n",
+             options.concurrency, options.connections, options.rps.map_or(String::from("unlimited"), |r| r.to_string()));
+
+    let path = grpc_client::path_of(service_request)?;
+    let (samples, elapsed) = grpc_client::runtime().block_on(run_workers(service_request, path, method, template, total, options))?;
+    print_report(&samples, elapsed);
+    Ok(samples.iter().all(|s| s.code == StatusCode::Ok))
+}
+
+async fn run_workers(
+    service_request: &ServiceRequest,
+    path: PathAndQuery,
+    method: MethodDescriptor,
+    template: BodyTemplate,
+    total: Option<u64>,
+    options: BenchOptions,
+) -> Result<(Vec<Sample>, Duration), String> {
+    let (target, timeouts) = (service_request.target(), service_request.timeouts);
+    let mut channels = vec![];
+    for _ in 0..options.connections {
+        channels.push(grpc_client::connect(&target, &timeouts, &service_request.network).await.map_err(|e| GrpcStatus::from(&e).to_string())?);
+    }
+
+    let started = Instant::now();
+    let pace = Pace { started, total, deadline: options.duration.map(|d| started + d), rps: options.rps };
+    let counter = Arc::new(AtomicU64::new(0));
+    let template = Arc::new(template);
+    let workers: Vec<_> = (0..options.concurrency).map(|i| {
+        let channel = channels[i % channels.len()].clone();
+        let (path, method, template, counter) = (path.clone(), method.clone(), template.clone(), counter.clone());
+        tokio::spawn(work(pace, counter, move |request_number| {
+            let (channel, path, method, template) = (channel.clone(), path.clone(), method.clone(), template.clone());
+            async move { send_one(channel, path, &method, &template, request_number, &timeouts).await }
+        }))
+    }).collect();
+
+    let mut samples = vec![];
+    for worker in workers {
+        samples.extend(worker.await.map_err(|e| format!("Worker failed: {}", e))?);
+    }
+    Ok((samples, started.elapsed()))
+}
+
+// when requests are sent, and when they stop
+#[derive(Debug, Clone, Copy)]
+struct Pace {
+    started: Instant,
+    total: Option<u64>,
+    deadline: Option<Instant>,
+    rps: Option<u64>,
+}
+
+impl Pace {
+    // with `rps`, requests are spread evenly from the start across every worker
+    fn due(&self, request_number: u64) -> Option<Instant> {
+        self.rps.map(|rps| self.started + Duration::from_secs_f64(request_number as f64 / rps.max(1) as f64))
+    }
+}
+
+// a worker taking request numbers from `counter` until `total` or the deadline
+async fn work<F, R>(pace: Pace, counter: Arc<AtomicU64>, send: F) -> Vec<Sample>
+where
+    F: Fn(u64) -> R,
+    R: Future<Output = Sample>,
+{
+    let mut samples = vec![];
+    loop {
+        let request_number = counter.fetch_add(1, Ordering::SeqCst);
+        if pace.total.is_some_and(|t| request_number >= t) { break; }
+        if let Some(due) = pace.due(request_number) {
+            tokio::time::sleep_until(due.into()).await;
+        }
+        if pace.deadline.is_some_and(|d| Instant::now() >= d) { break; }
+        samples.push(send(request_number).await);
+    }
+    samples
+}
+
+async fn send_one(channel: Channel, path: PathAndQuery, method: &MethodDescriptor, template: &BodyTemplate, request_number: u64, timeouts: &Timeouts) -> Sample {
+    let started = Instant::now();
+    let messages = match grpc_client::encode_body(method, &template.render(request_number)) {
+        Ok(m) => m,
+        Err(_) => return Sample { latency: started.elapsed(), code: StatusCode::InvalidArgument },
+    };
+    let result: Result<(), tonic::Status> = grpc_client::with_deadline(timeouts.deadline, async {
+        let mut grpc = tonic::client::Grpc::new(channel);
+        grpc.ready().await.map_err(|e| tonic::Status::unavailable(e.to_string()))?;
+        let request = grpc_client::new_request(tokio_stream::iter(messages), timeouts);
+        let response = grpc.streaming(request, path, DynamicCodec::new(method.output())).await?;
+        let mut stream = response.into_inner();
+        while stream.message().await?.is_some() {}
+        stream.trailers().await?;
+        Ok(())
+    }).await;
+    let code = result.map_or_else(|status| StatusCode::from(status.code() as i32), |_| StatusCode::Ok);
+    Sample { latency: started.elapsed(), code }
+}
+
+fn print_report(samples: &[Sample], elapsed: Duration) {
+    let mut stats = LatencyStats::new();
+    samples.iter().for_each(|s| stats.push(s.latency));
+
+    println!("{}", "Summary:".bold());
+    println!("  Count:      {}", samples.len());
+    println!("  Total:      {}", format_duration(elapsed));
+    println!("  Throughput: {:.2} req/s", samples.len() as f64 / elapsed.as_secs_f64().max(f64::EPSILON));
+    println!("  Latency:    {}", stats);
+
+    println!("\n{}", "Latency distribution:".bold());
+    for p in [10.0, 25.0, 50.0, 75.0, 90.0, 95.0, 99.0] {
+        println!("  p{:<3} {}", p, format_duration(stats.percentile(p)));
+    }
+
+    println!("\n{}", "Histogram:".bold());
+    print_histogram(samples, &stats);
+
+    println!("\n{}", "Status code distribution:".bold());
+    for (code, count) in code_counts(samples) {
+        let line = format!("  [{}] {} responses", code, count);
+        println!("{}", if code == StatusCode::Ok { line.green() } else { line.red() });
+    }
+}
+
+// in the order of the codes
+fn code_counts(samples: &[Sample]) -> Vec<(StatusCode, usize)> {
+    let mut codes: BTreeMap<i32, (StatusCode, usize)> = BTreeMap::new();
+    samples.iter().for_each(|s| codes.entry(s.code.code()).or_insert((s.code, 0)).1 += 1);
+    codes.into_values().collect()
+}
+
+fn print_histogram(samples: &[Sample], stats: &LatencyStats) {
+    const BAR_WIDTH: usize = 40;
+    let buckets = histogram(samples, stats);
+    let largest = buckets.iter().map(|(_, count)| *count).max().unwrap_or(1).max(1);
+    for (upper, count) in buckets {
+        let bar = "■".repeat(count * BAR_WIDTH / largest);
+        println!("  {:>10} [{:>6}] {}", format_duration(upper), count, bar);
+    }
+}
+
+// the upper bound and the count of each of the equal width buckets between the fastest and the slowest
+fn histogram(samples: &[Sample], stats: &LatencyStats) -> Vec<(Duration, usize)> {
+    const BUCKETS: usize = 10;
+    if samples.is_empty() { return vec![]; }
+    let (min, max) = (stats.min().as_secs_f64(), stats.max().as_secs_f64());
+    let width = ((max - min) / BUCKETS as f64).max(f64::EPSILON);
+    let mut counts = [0usize; BUCKETS];
+    for s in samples {
+        let index = ((s.latency.as_secs_f64() - min) / width) as usize;
+        counts[index.min(BUCKETS - 1)] += 1;
+    }
+    counts.iter().enumerate()
+        .map(|(i, count)| (Duration::from_secs_f64(min + width * (i + 1) as f64), *count))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(millis: u64, code: StatusCode) -> Sample {
+        Sample { latency: Duration::from_millis(millis), code }
+    }
+
+    fn stats_of(samples: &[Sample]) -> LatencyStats {
+        let mut stats = LatencyStats::new();
+        samples.iter().for_each(|s| stats.push(s.latency));
+        stats
+    }
+
+    #[test]
+    fn latencies_into_buckets() {
+        let samples = (1..=10).map(|ms| sample(ms * 10, StatusCode::Ok)).chain([sample(100, StatusCode::Ok)]).collect::<Vec<Sample>>();
+        let buckets = histogram(&samples, &stats_of(&samples));
+        assert_eq!(buckets.iter().map(|(_, count)| *count).collect::<Vec<usize>>(), vec![1, 1, 1, 1, 1, 1, 1, 1, 1, 2]);
+        assert_eq!(buckets[0].0.as_millis(), 19);
+        assert_eq!(buckets[9].0.as_millis(), 100);
+
+        let same = (0..3).map(|_| sample(5, StatusCode::Ok)).collect::<Vec<Sample>>();
+        assert_eq!(histogram(&same, &stats_of(&same))[0].1, 3);
+        assert!(histogram(&[], &LatencyStats::new()).is_empty());
+    }
+
+    #[test]
+    fn status_code_breakdown() {
+        let samples = [
+            sample(1, StatusCode::Unavailable),
+            sample(1, StatusCode::Ok),
+            sample(1, StatusCode::DeadlineExceeded),
+            sample(1, StatusCode::Unavailable),
+            sample(1, StatusCode::Ok),
+        ];
+        assert_eq!(code_counts(&samples), vec![(StatusCode::Ok, 2), (StatusCode::DeadlineExceeded, 1), (StatusCode::Unavailable, 2)]);
+    }
+
+    fn run_pace(pace: Pace, workers: usize, latency: Duration) -> Vec<u64> {
+        let sent = Arc::new(std::sync::Mutex::new(vec![]));
+        grpc_client::runtime().block_on(async {
+            let counter = Arc::new(AtomicU64::new(0));
+            let handles = (0..workers).map(|_| {
+                let sent = sent.clone();
+                tokio::spawn(work(pace, counter.clone(), move |request_number| {
+                    sent.lock().unwrap().push(request_number);
+                    async move {
+                        tokio::time::sleep(latency).await;
+                        Sample { latency, code: StatusCode::Ok }
+                    }
+                }))
+            }).collect::<Vec<_>>();
+            for handle in handles {
+                handle.await.unwrap();
+            }
+        });
+        let mut sent = sent.lock().unwrap().clone();
+        sent.sort();
+        sent
+    }
+
+    #[test]
+    fn stops_at_total() {
+        let pace = Pace { started: Instant::now(), total: Some(10), deadline: None, rps: None };
+        assert_eq!(run_pace(pace, 3, Duration::ZERO), (0..10).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn stops_at_duration() {
+        let started = Instant::now();
+        let pace = Pace { started, total: None, deadline: Some(started + Duration::from_millis(50)), rps: None };
+        let sent = run_pace(pace, 2, Duration::from_millis(10));
+        assert!(!sent.is_empty());
+        // a request in flight at the deadline finishes, and no other is sent
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert!(sent.len() <= 2 * 6, "{:?}", sent);
+    }
+
+    #[test]
+    fn rps_spreads_requests() {
+        let started = Instant::now();
+        let pace = Pace { started, total: Some(5), deadline: None, rps: Some(100) };
+        assert_eq!(pace.due(0), Some(started));
+        assert_eq!(pace.due(4), Some(started + Duration::from_millis(40)));
+        assert_eq!(Pace { rps: None, ..pace }.due(4), None);
+        assert_eq!(run_pace(pace, 2, Duration::ZERO), vec![0, 1, 2, 3, 4]);
+        assert!(started.elapsed() >= Duration::from_millis(40));
+
+        // the deadline cuts requests due after it
+        let started = Instant::now();
+        let pace = Pace { started, total: None, deadline: Some(started + Duration::from_millis(125)), rps: Some(20) };
+        assert_eq!(run_pace(pace, 1, Duration::ZERO), vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,126 @@
+use prost_reflect::{FieldDescriptor, Kind, MessageDescriptor};
+use serde_json::{json, Map, Value};
+
+// A request body with every field of a message filled with an example value, as JSON
+pub fn skeleton_of(message: &MessageDescriptor) -> Value {
+    skeleton(message, &mut vec![])
+}
+
+// `visiting` stops recursive messages from expanding forever
+fn skeleton(message: &MessageDescriptor, visiting: &mut Vec<String>) -> Value {
+    if let Some(value) = well_known(message.full_name()) {
+        return value;
+    }
+    if visiting.iter().any(|m| m == message.full_name()) {
+        return json!({});
+    }
+    visiting.push(message.full_name().to_string());
+    let mut fields = Map::new();
+    for field in message.fields() {
+        // only the first member of a oneof, as setting more of them is invalid
+        if let Some(oneof) = field.containing_oneof().filter(|o| !o.is_synthetic()) {
+            if oneof.fields().next().is_some_and(|first| first.number() != field.number()) {
+                continue;
+            }
+        }
+        fields.insert(field.json_name().to_string(), field_skeleton(&field, visiting));
+    }
+    visiting.pop();
+    Value::Object(fields)
+}
+
+fn field_skeleton(field: &FieldDescriptor, visiting: &mut Vec<String>) -> Value {
+    if field.is_map() {
+        let entry = match field.kind() {
+            Kind::Message(entry) => entry,
+            _ => return json!({}),
+        };
+        let key = match kind_skeleton(&entry.map_entry_key_field().kind(), visiting) {
+            Value::String(s) if s.is_empty() => String::from("key"),
+            key => key.to_string().trim_matches('"').to_string(),
+        };
+        let value = kind_skeleton(&entry.map_entry_value_field().kind(), visiting);
+        json!({ key: value })
+    } else if field.is_list() {
+        json!([kind_skeleton(&field.kind(), visiting)])
+    } else {
+        kind_skeleton(&field.kind(), visiting)
+    }
+}
+
+fn kind_skeleton(kind: &Kind, visiting: &mut Vec<String>) -> Value {
+    match kind {
+        Kind::Double | Kind::Float => json!(0.0),
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 | Kind::Uint32 | Kind::Fixed32 => json!(0),
+        // 64 bit integers are strings in JSON
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 | Kind::Uint64 | Kind::Fixed64 => json!("0"),
+        Kind::Bool => json!(false),
+        Kind::String => json!(""),
+        Kind::Bytes => json!(""),
+        Kind::Enum(e) => json!(e.default_value().name()),
+        Kind::Message(m) => skeleton(m, visiting),
+    }
+}
+
+fn well_known(full_name: &str) -> Option<Value> {
+    match full_name {
+        "google.protobuf.Timestamp" => Some(json!("1970-01-01T00:00:00Z")),
+        "google.protobuf.Duration" => Some(json!("0s")),
+        "google.protobuf.FieldMask" => Some(json!("")),
+        "google.protobuf.Struct" => Some(json!({})),
+        "google.protobuf.Value" => Some(Value::Null),
+        "google.protobuf.ListValue" => Some(json!([])),
+        "google.protobuf.Empty" => Some(json!({})),
+        "google.protobuf.Any" => Some(json!({ "@type": "" })),
+        "google.protobuf.StringValue" | "google.protobuf.BytesValue" => Some(json!("")),
+        "google.protobuf.BoolValue" => Some(json!(false)),
+        "google.protobuf.Int64Value" | "google.protobuf.UInt64Value" => Some(json!("0")),
+        "google.protobuf.Int32Value" | "google.protobuf.UInt32Value" => Some(json!(0)),
+        "google.protobuf.DoubleValue" | "google.protobuf.FloatValue" => Some(json!(0.0)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_reflect::{DescriptorPool, DynamicMessage};
+    use prost_types::field_descriptor_proto::{Label, Type};
+    use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet};
+
+    fn field(name: &str, number: i32, r#type: Type, label: Label, type_name: Option<&str>) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(String::from(name)),
+            number: Some(number),
+            label: Some(label as i32),
+            r#type: Some(r#type as i32),
+            type_name: type_name.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn skeleton_is_a_valid_body() {
+        let file = FileDescriptorProto {
+            name: Some(String::from("tree.proto")),
+            package: Some(String::from("tree")),
+            syntax: Some(String::from("proto3")),
+            message_type: vec![DescriptorProto {
+                name: Some(String::from("Node")),
+                field: vec![
+                    field("node_id", 1, Type::Int64, Label::Optional, None),
+                    field("name", 2, Type::String, Label::Optional, None),
+                    field("children", 3, Type::Message, Label::Repeated, Some(".tree.Node")),
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let pool = DescriptorPool::from_file_descriptor_set(FileDescriptorSet { file: vec![file] }).unwrap();
+        let node = pool.get_message_by_name("tree.Node").unwrap();
+        let skeleton = skeleton_of(&node);
+
+        assert_eq!(skeleton, json!({ "nodeId": "0", "name": "", "children": [{}] }));
+        assert!(DynamicMessage::deserialize(node, &skeleton).is_ok());
+    }
+}
@@ -0,0 +1,125 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+// Placeholders replaced for every request
+//   {{request_number}}, {{timestamp}}, {{timestamp_ms}}, {{uuid}}, {{random_int MIN MAX}}, {{random_string LENGTH}}
+#[derive(Debug, PartialEq, Clone)]
+enum Placeholder {
+    RequestNumber,
+    Timestamp,
+    TimestampMillis,
+    Uuid,
+    RandomInt(i64, i64),
+    RandomString(usize),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum TemplatePart {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BodyTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+impl BodyTemplate {
+    pub fn from(s: &str) -> Result<BodyTemplate, String> {
+        let mut parts = vec![];
+        let mut rest = s;
+        while let Some(start) = rest.find("{{") {
+            parts.push(TemplatePart::Literal(String::from(&rest[..start])));
+            let end = rest[start..].find("}}")
+                .ok_or_else(|| format!("Unclosed placeholder at `{}`", &rest[start..]))? + start;
+            parts.push(TemplatePart::Placeholder(parse_placeholder(&rest[start + 2..end])?));
+            rest = &rest[end + 2..];
+        }
+        parts.push(TemplatePart::Literal(String::from(rest)));
+        Ok(BodyTemplate { parts })
+    }
+
+    pub fn render(&self, request_number: u64) -> String {
+        let mut rng = rand::thread_rng();
+        self.parts.iter().map(|part| match part {
+            TemplatePart::Literal(s) => s.clone(),
+            TemplatePart::Placeholder(p) => match p {
+                Placeholder::RequestNumber => request_number.to_string(),
+                Placeholder::Timestamp => now().as_secs().to_string(),
+                Placeholder::TimestampMillis => now().as_millis().to_string(),
+                Placeholder::Uuid => {
+                    let bytes: [u8; 16] = rng.gen();
+                    uuid_v4(bytes)
+                }
+                Placeholder::RandomInt(min, max) => rng.gen_range(*min..=*max).to_string(),
+                Placeholder::RandomString(length) => (&mut rng).sample_iter(&Alphanumeric).take(*length).map(char::from).collect(),
+            },
+        }).collect()
+    }
+}
+
+fn parse_placeholder(s: &str) -> Result<Placeholder, String> {
+    let words: Vec<&str> = s.split_whitespace().collect();
+    let number = |i: usize| -> Result<i64, String> {
+        words.get(i)
+            .ok_or_else(|| format!("Missing argument for `{}`", s.trim()))?
+            .parse::<i64>()
+            .map_err(|_| format!("Invalid number in `{}`", s.trim()))
+    };
+    match words.first().copied() {
+        Some("request_number") => Ok(Placeholder::RequestNumber),
+        Some("timestamp") => Ok(Placeholder::Timestamp),
+        Some("timestamp_ms") => Ok(Placeholder::TimestampMillis),
+        Some("uuid") => Ok(Placeholder::Uuid),
+        Some("random_int") => {
+            let (min, max) = (number(1)?, number(2)?);
+            if min > max { Err(format!("Invalid range in `{}`", s.trim())) } else { Ok(Placeholder::RandomInt(min, max)) }
+        }
+        Some("random_string") => Ok(Placeholder::RandomString(number(1)?.max(0) as usize)),
+        _ => Err(format!("Unknown placeholder `{{{{{}}}}}`", s.trim())),
+    }
+}
+
+fn now() -> std::time::Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+fn uuid_v4(mut bytes: [u8; 16]) -> String {
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_placeholders() {
+        let template = BodyTemplate::from("{\"id\": {{request_number}}, \"n\": {{ random_int 3 3 }}, \"s\": \"{{random_string 4}}\"}").unwrap();
+        let rendered: serde_json::Value = serde_json::from_str(&template.render(7)).unwrap();
+        assert_eq!(rendered["id"], 7);
+        assert_eq!(rendered["n"], 3);
+        assert_eq!(rendered["s"].as_str().map(|s| s.len()), Some(4));
+    }
+
+    #[test]
+    fn body_without_placeholder_is_kept() {
+        assert_eq!(BodyTemplate::from("{\"a\": {\"b\": 1}}").unwrap().render(0), "{\"a\": {\"b\": 1}}");
+    }
+
+    #[test]
+    fn invalid_placeholders() {
+        assert!(BodyTemplate::from("{{unknown}}").is_err());
+        assert!(BodyTemplate::from("{{random_int 5 1}}").is_err());
+        assert!(BodyTemplate::from("{{uuid").is_err());
+    }
+
+    #[test]
+    fn uuid_format() {
+        let uuid = uuid_v4([0xff; 16]);
+        assert_eq!(uuid, "ffffffff-ffff-4fff-bfff-ffffffffffff");
+    }
+}
@@ -0,0 +1,32 @@
+use crate::grpc_client;
+use crate::grpc_status::StatusCode;
+use crate::grpc_request_dsl::ServiceRequest;
+use crate::response_filter::Filter;
+use crate::session::Session;
+use crate::settings::Settings;
+use crate::text_coloring::{to_error, to_warn};
+use crate::util::to_json;
+
+pub fn run(target: &str, method: &str, data: Option<String>, filter: Option<String>, settings: &Settings) -> Result<bool, String> {
+    let mut service_request = ServiceRequest::parse(target, method, settings.network())?;
+    service_request.update_timeouts(settings.timeouts);
+    service_request.update_retry_policy(settings.retry.clone());
+    if let Some(body) = data {
+        service_request.update_body(to_json(&body)?.to_string());
+    }
+
+    let mut session = Session::new(settings.output.format);
+    session.set_filter(filter.map(|f| Filter::from(&f)).transpose()?)?;
+    let response = grpc_client::request(&service_request)?;
+    session.last_response = response.messages;
+    session.print_response(&service_request)?;
+    match response.status.code {
+        StatusCode::Ok => {}
+        StatusCode::DeadlineExceeded => eprintln!("{}\n{}", to_warn(&response.status.to_string()), to_warn("No response within the deadline. Set a longer one with --deadline")),
+        _ => eprintln!("{}", to_error(&response.status.to_string())),
+    }
+    if response.attempts > 1 {
+        eprintln!("Attempts: {}", response.attempts);
+    }
+    Ok(response.status.is_ok())
+}
@@ -0,0 +1,162 @@
+use clap::{ArgAction, Parser, Subcommand};
+
+#[derive(Debug, Parser)]
+#[command(name = "zrpc-cli", version, about = "Reflection based grpc client")]
+pub struct Cli {
+    /// Config file, layered over the ones found in the usual places
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<String>,
+
+    /// Response output format: pretty, compact, yaml, table, raw, hex
+    #[arg(long, global = true, value_name = "FORMAT")]
+    pub output: Option<String>,
+
+    /// Deadline of each request, sent as `grpc-timeout`. `0` for none. ex) `500ms`, `30s`
+    #[arg(long, global = true)]
+    pub deadline: Option<String>,
+
+    /// Timeout for establishing a connection. ex) `3s`
+    #[arg(long, global = true)]
+    pub connect_timeout: Option<String>,
+
+    /// Attempts per request including the first one. `1` for no retry
+    #[arg(long, global = true, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_attempts: Option<u32>,
+
+    /// Status codes to retry on. ex) `UNAVAILABLE,RESOURCE_EXHAUSTED`
+    #[arg(long, global = true)]
+    pub retry_on: Option<String>,
+
+    /// Log more, over `log_level` in config. `-v` for debug, `-vv` for trace
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Start from a profile in config, skipping the prompts it answers
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<SubCommand>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SubCommand {
+    /// Send a single request and print the response
+    Call {
+        /// Server address. ex) `localhost:9090`
+        target: String,
+        /// Function to call. ex) `helloworld.Greeter/SayHello`
+        method: String,
+        /// Request body in JSON
+        #[arg(short, long)]
+        data: Option<String>,
+        /// jq style filter applied to each response message. ex) `.items[] | .id`
+        #[arg(long)]
+        filter: Option<String>,
+    },
+    /// Load test a function with the built-in client
+    Bench {
+        /// Server address. ex) `localhost:9090`
+        target: String,
+        /// Function to call. ex) `helloworld.Greeter/SayHello`
+        method: String,
+        /// Request body in JSON. Placeholders vary it per request:
+        /// {{request_number}}, {{timestamp}}, {{timestamp_ms}}, {{uuid}}, {{random_int MIN MAX}}, {{random_string LENGTH}}
+        #[arg(short, long, verbatim_doc_comment)]
+        data: Option<String>,
+        /// Number of requests to send. Defaults to 200 unless `--duration` is given
+        #[arg(short = 'n', long)]
+        total: Option<u64>,
+        /// How long to keep sending. ex) `30s`, `5m`
+        #[arg(long)]
+        duration: Option<String>,
+        /// Requests per second across all workers. Unlimited if not given
+        #[arg(long)]
+        rps: Option<u64>,
+        /// Number of requests in flight at once
+        #[arg(short, long, default_value_t = 10)]
+        concurrency: usize,
+        /// Number of connections shared by the workers
+        #[arg(long, default_value_t = 1)]
+        connections: usize,
+    },
+    /// Compare schemas of two servers(or protoset files) and fail on breaking changes
+    SchemaDiff {
+        /// Target or protoset file of the current schema. ex) `prod:443`, `prod.protoset`
+        old: String,
+        /// Target or protoset file of the new schema
+        new: String,
+    },
+    /// Write the schema of a target(or protoset file) as `.proto` files or a protoset
+    Export {
+        /// Target or protoset file. ex) `localhost:9090`
+        source: String,
+        /// `proto` or `protoset`
+        #[arg(long, default_value = "proto")]
+        format: String,
+        /// Directory for `.proto` files(default `proto`), or file for a protoset(default `schema.protoset`)
+        #[arg(long, short)]
+        out: Option<String>,
+    },
+    /// Write API documentation of every service, message and enum of a target(or protoset file)
+    Docs {
+        /// Target or protoset file. ex) `localhost:9090`
+        source: String,
+        /// `markdown` or `html`
+        #[arg(long, default_value = "markdown")]
+        format: String,
+        /// Output file. `api.md` or `api.html` by default
+        #[arg(long, short)]
+        out: Option<String>,
+    },
+    /// Serve every method of a schema with responses from rules, or generated ones
+    Mock {
+        /// Protoset file(or target) with the schema to serve
+        #[arg(long)]
+        protoset: String,
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        #[arg(long, default_value_t = 9090)]
+        port: u16,
+        /// Rules file(one JSON rule per line)
+        #[arg(long)]
+        rules: Option<String>,
+    },
+    /// Proxy calls to a server, appending each of them to a collection
+    Record {
+        /// Address to listen on. ex) `:9091`, `127.0.0.1:9091`
+        #[arg(long, default_value = ":9091")]
+        listen: String,
+        /// Server to proxy to. ex) `localhost:9090`
+        #[arg(long)]
+        upstream: String,
+        /// Collection file the calls are appended to
+        #[arg(short, long, default_value = "requests.jsonl")]
+        out: String,
+        /// Also append each call as a mock rule to this file
+        #[arg(long)]
+        rules: Option<String>,
+    },
+    /// Append requests from grpcurl command lines, or a Postman or Insomnia export, to a collection
+    Import {
+        /// File with grpcurl command lines, or an exported JSON
+        source: String,
+        /// Collection file the requests are appended to
+        #[arg(short, long, default_value = "requests.jsonl")]
+        out: String,
+    },
+    /// Record responses of a collection to golden files, or compare against them
+    Snapshot {
+        /// Collection file(one JSON request per line)
+        collection: String,
+        /// Directory holding the golden files
+        #[arg(long, default_value = "snapshots")]
+        dir: String,
+        /// Path to ignore while comparing. ex) `createdAt`, `items[*].id`
+        #[arg(long = "ignore", value_name = "PATH")]
+        ignore: Vec<String>,
+        /// Accept current responses as the new golden files
+        #[arg(long)]
+        update: bool,
+    },
+}
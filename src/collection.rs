@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::grpc_request_dsl::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionEntry {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub service: String,
+    pub function: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub tls: bool,
+    // an array is a stream of messages
+    #[serde(default = "empty_body")]
+    pub body: Value,
+    // sent with the request. Keys ending with `-bin` take base64 values
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    // descriptors from files, for servers without reflection
+    #[serde(default, flatten)]
+    pub schema: SchemaFiles,
+    // the rest is what a recorded call saw, kept for reference
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elapsed_ms: Option<u64>,
+}
+
+fn empty_body() -> Value {
+    Value::Object(serde_json::Map::new())
+}
+
+impl CollectionEntry {
+    pub fn to_service_request(&self) -> Result<ServiceRequest, String> {
+        let mut request = ServiceRequest::new(Host::from(self.host.clone())?);
+        request.update_port(Port(self.port));
+        request.update_service(ServiceName::from(&self.service));
+        request.update_function(ServiceFunction::from(&self.function));
+        request.tls = self.tls;
+        request.update_body(match &self.body {
+            Value::Array(messages) => messages.iter().map(|m| m.to_string()).collect::<Vec<String>>().join("\n"),
+            body => body.to_string(),
+        });
+        request.metadata = self.metadata.clone();
+        request.schema = self.schema.clone();
+        Ok(request)
+    }
+}
+
+pub fn load(path: &str) -> Result<Vec<CollectionEntry>, String> {
+    load_jsonl(path)
+}
+
+// one JSON value per line, blank lines are skipped
+pub fn load_jsonl<T: DeserializeOwned>(path: &str) -> Result<Vec<T>, String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("Failed to open `{}`: {}", path, e))?;
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.as_ref().map_or(true, |l| !l.trim().is_empty()))
+        .map(|(i, line)| {
+            let line = line.map_err(|e| format!("Failed to read `{}`: {}", path, e))?;
+            serde_json::from_str::<T>(&line)
+                .map_err(|e| format!("Invalid entry at {}:{}: {}", path, i + 1, e))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_to_request_and_back() {
+        let line = r#"{"name": "get", "host": "api.internal", "port": 443, "service": "users.Users", "function": "Get", "tls": true, "body": {"id": 1}, "metadata": {"authorization": "Bearer t", "trace-bin": "AQI="}, "protosets": ["users.protoset"]}"#;
+        let entry: CollectionEntry = serde_json::from_str(line).unwrap();
+        let request = entry.to_service_request().unwrap();
+        assert_eq!(request.metadata, entry.metadata);
+        assert_eq!(request.schema.protosets, vec![String::from("users.protoset")]);
+        assert_eq!((request.tls, request.body.0.as_str()), (true, r#"{"id":1}"#));
+
+        let saved: CollectionEntry = serde_json::from_str(&serde_json::to_string(&entry).unwrap()).unwrap();
+        assert_eq!(saved.to_service_request().unwrap().metadata.get("authorization").map(String::as_str), Some("Bearer t"));
+        assert_eq!(saved.schema, entry.schema);
+    }
+}
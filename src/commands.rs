@@ -10,14 +10,19 @@ pub enum Commands {
     TakeBodyInput,
     SendRequest,
     EndOfRequestSelection,
+    SelectOutputFormat,
+    SetResponseFilter,
+    SetTimeouts,
+    RefreshSchema,
+    CopyAs,
     Exit
 }
 impl Commands {
     pub fn get_command_message(&self) -> Option<String> {
         match self {
             Commands::Exit                    => None,
-            Commands::UpdateHost              => Some(String::from("Type Host or `Enter` for \"localhost\"")),
-            Commands::TakePortInput           => Some(String::from("Type Port or `Enter` for \"9090\"")),
+            Commands::UpdateHost              => Some(String::from("Type Host, Host:Port(ex. `api:8443`, `grpcs://api:443`, `grpc-web+https://api:8443`, `unix:/path`) or `Enter` for the default")),
+            Commands::TakePortInput           => Some(String::from("Type Port or `Enter` for the default")),
             Commands::SendServiceListRequest  => Some(String::from("Select service to proceed")),
             Commands::SendFunctionListRequest => Some(String::from("Select function to proceed")),
             Commands::TakeBodyInput           => Some(String::from("Type request body\nType 3 new lines in order to finish(`Enter` 3 times)\nex) {\"name\": \"Johnny\"}")),
@@ -29,9 +34,19 @@ impl Commands {
     4. Set Function
     5. Set body
     6. Repeat(or Enter)
+    7. Change output format
+    8. Set response filter
+    9. Set deadline and connect timeout
+    10. Refresh schema
+    11. Copy as grpcurl, buf curl or code
     -------------------
-    7. to exit(or 'exit')\n"
+    12. to exit(or 'exit')\n"
             )),
+            Commands::SelectOutputFormat      => Some(String::from("Select output format")),
+            Commands::SetTimeouts             => Some(String::from("Type deadline(`0` for none) then connect timeout, or `Enter` to keep current\nex) 500ms, 30s, 1m")),
+            Commands::RefreshSchema           => Some(String::from("Refreshing schema")),
+            Commands::CopyAs                  => Some(String::from("Select what to copy the request as")),
+            Commands::SetResponseFilter       => Some(String::from("Type response filter or `Enter` to clear\nex) .items[] | select(.price > 10) | {id, name}")),
         }
     }
     pub fn print_command_message(&self) -> () {
@@ -40,7 +55,7 @@ impl Commands {
                 let colored_message = match self {
                     Commands::UpdateHost | Commands::TakePortInput | Commands::SendServiceListRequest | Commands::SendFunctionListRequest | Commands::TakeBodyInput => to_plain_msg(&message),
                     Commands::SendRequest => to_plain_msg(&message),
-                    Commands::EndOfRequestSelection => to_plain_msg(&message),
+                    Commands::EndOfRequestSelection | Commands::SelectOutputFormat | Commands::SetResponseFilter | Commands::SetTimeouts | Commands::RefreshSchema | Commands::CopyAs => to_plain_msg(&message),
                     _ => to_plain(&message),
                 };
                 println!("{}", colored_message);
@@ -62,6 +77,11 @@ impl Commands {
             Commands::TakeBodyInput           => Commands::SendRequest,
             Commands::SendRequest             => Commands::EndOfRequestSelection,
             Commands::EndOfRequestSelection   => Commands::Exit,
+            Commands::SelectOutputFormat      => Commands::EndOfRequestSelection,
+            Commands::SetResponseFilter       => Commands::EndOfRequestSelection,
+            Commands::SetTimeouts             => Commands::EndOfRequestSelection,
+            Commands::RefreshSchema           => Commands::EndOfRequestSelection,
+            Commands::CopyAs                  => Commands::EndOfRequestSelection,
             _ => unreachable!()
         }
     }
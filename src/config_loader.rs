@@ -1,30 +1,117 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use config::*;
+use crate::settings;
 
-pub fn config() -> Config {
-    config_from_path("/var/zrpc-cli/config.json")
+// Config in layers, each overriding the ones before it
+//   defaults
+//   /var/zrpc-cli/config.json, where it used to be
+//   /etc/zrpc-cli/config.{json,toml,yaml}
+//   $XDG_CONFIG_HOME/zrpc-cli/config.{json,toml,yaml}
+//   ./.zrpc.{json,toml,yaml}
+//   $ZRPC_CONFIG
+//   --config
+//   ZRPC_* environment variables, with `__` between sections. ex) ZRPC_TIMEOUTS__CONNECT=3s
+
+pub const DEFAULTS: &str = r#"
+log_level = "info"
+log.destination = "stderr"
+log.format = "text"
+log.max_size = "10MB"
+log.max_files = 5
+auto_correction.max_attempt = 5
+defaults.host = "localhost"
+defaults.port = 9090
+defaults.tls = false
+output.format = "pretty"
+output.color = "auto"
+timeouts.connect = "10s"
+timeouts.deadline = "30s"
+retry.max_attempts = 1
+retry.codes = "UNAVAILABLE"
+retry.initial_backoff = "100ms"
+retry.max_backoff = "5s"
+retry.multiplier = 2.0
+descriptor_cache.ttl = "10m"
+"#;
+
+const EXTENSIONS: [&str; 3] = ["json", "toml", "yaml"];
+
+pub struct LoadedConfig {
+    pub config: Config,
+    // the files that were found, in the order they were layered
+    pub files: Vec<PathBuf>,
+}
+
+pub fn config(explicit: Option<&str>) -> Result<LoadedConfig, String> {
+    let mut files: Vec<PathBuf> = vec![PathBuf::from("/var/zrpc-cli/config.json")];
+    files.extend(EXTENSIONS.iter().map(|e| PathBuf::from(format!("/etc/zrpc-cli/config.{}", e))));
+    if let Some(dir) = dirs::config_dir() {
+        files.extend(EXTENSIONS.iter().map(|e| dir.join("zrpc-cli").join(format!("config.{}", e))));
+    }
+    files.extend(EXTENSIONS.iter().map(|e| PathBuf::from(format!(".zrpc.{}", e))));
+    let mut files: Vec<PathBuf> = files.into_iter().filter(|f| f.is_file()).collect();
+
+    // asked for by name, so they must be there
+    let env_config = std::env::var("ZRPC_CONFIG").ok().filter(|p| !p.is_empty());
+    for path in env_config.iter().map(String::as_str).chain(explicit) {
+        if !Path::new(path).is_file() {
+            return Err(format!("Config file `{}` not found", path));
+        }
+        files.push(PathBuf::from(path));
+    }
+
+    let overrides = std::env::vars().filter(|(k, _)| is_override(k)).collect();
+    Ok(LoadedConfig { config: build(&files, overrides)?, files })
 }
 
-pub fn config_from_path(s: &str) -> Config {
-    Config::builder()
-        .add_source(File::with_name(s))
-        .add_source(config::File::from_str(
-            r#"
-            log_level = "info"
-            auto_correction.max_attempt = 5
-            "#,
-            config::FileFormat::Toml,
-        ))
+// `ZRPC_*` of a section of settings, leaving out `ZRPC_CONFIG` and variables of other tools. ex) ZRPC_TOKEN
+fn is_override(name: &str) -> bool {
+    name.strip_prefix("ZRPC_")
+        .and_then(|key| key.split("__").next())
+        .is_some_and(|section| settings::is_section(&section.to_lowercase()))
+}
+
+fn build(files: &[PathBuf], overrides: HashMap<String, String>) -> Result<Config, String> {
+    let mut builder = Config::builder().add_source(File::from_str(DEFAULTS, FileFormat::Toml));
+    for file in files {
+        builder = builder.add_source(File::from(file.as_path()).required(true));
+    }
+    builder
+        .add_source(Environment::with_prefix("ZRPC").prefix_separator("_").separator("__").source(Some(overrides)))
         .build()
-        .unwrap_or_else(|_| {
-            Config::builder()
-                .add_source(config::File::from_str(
-                    r#"
-                    log_level = "info"
-                    auto_correction.max_attempt = 5
-                    "#,
-                    config::FileFormat::Toml,
-                ))
-                .build()
-                .unwrap()
-        })
+        .map_err(|e| format!("config error : {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_layers_override() {
+        let dir = std::env::temp_dir().join(format!("zrpc-cli-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (global, local) = (dir.join("config.toml"), dir.join(".zrpc.json"));
+        std::fs::write(&global, "log_level = \"debug\"\n[timeouts]\nconnect = \"3s\"\ndeadline = \"5s\"\n").unwrap();
+        std::fs::write(&local, r#"{"timeouts": {"deadline": "1s"}}"#).unwrap();
+        let overrides = HashMap::from([(String::from("ZRPC_TIMEOUTS__CONNECT"), String::from("2s"))]);
+        let config = build(&[global, local], overrides).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(config.get_string("log_level").unwrap(), "debug");
+        assert_eq!(config.get_string("timeouts.deadline").unwrap(), "1s");
+        assert_eq!(config.get_string("timeouts.connect").unwrap(), "2s");
+        assert_eq!(config.get_string("descriptor_cache.ttl").unwrap(), "10m");
+    }
+
+    #[test]
+    fn overrides_of_sections_only() {
+        assert!(is_override("ZRPC_LOG_LEVEL"));
+        assert!(is_override("ZRPC_TIMEOUTS__CONNECT"));
+        assert!(is_override("ZRPC_TIMEOUTS__CONECT"));
+        assert!(is_override("ZRPC_PROFILES__STAGING__HOST"));
+        assert!(!is_override("ZRPC_CONFIG"));
+        assert!(!is_override("ZRPC_TOKEN"));
+        assert!(!is_override("GRPC_TIMEOUTS__CONNECT"));
+    }
 }
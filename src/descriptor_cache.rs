@@ -0,0 +1,119 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::descriptors;
+use crate::grpc_request_dsl::{Network, Target, Timeouts};
+
+// Descriptors fetched through reflection, kept on disk per target
+//   <cache dir>/zrpc-cli/descriptors/<target>.protoset and <target>.json
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CacheInfo {
+    target: String,
+    fetched_at: u64,
+    sha256: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Refreshed {
+    Unchanged,
+    Changed { previous: Option<String>, current: String },
+}
+
+// cached descriptors when fresh. Otherwise fetched again, falling back to stale ones when reflection fails
+pub fn protoset_of(target: &Target, timeouts: &Timeouts, network: &Network) -> Result<Vec<u8>, String> {
+    let cached = load(target);
+    if let Some((info, protoset)) = &cached {
+        if is_fresh(info.fetched_at, now(), network.descriptor_cache_ttl) {
+            debug!("Using cached descriptors of `{}`", target);
+            return Ok(protoset.clone());
+        }
+    }
+    match fetch_and_save(target, timeouts, network) {
+        Ok((_, protoset)) => Ok(protoset),
+        Err(e) => match cached {
+            Some((info, protoset)) => {
+                warn!("{}. Using descriptors cached {} seconds ago", e, now().saturating_sub(info.fetched_at));
+                Ok(protoset)
+            }
+            None => Err(e),
+        },
+    }
+}
+
+pub fn refresh(target: &Target, timeouts: &Timeouts, network: &Network) -> Result<Refreshed, String> {
+    let previous = load(target).map(|(info, _)| info.sha256);
+    let (info, _) = fetch_and_save(target, timeouts, network)?;
+    if previous.as_ref() == Some(&info.sha256) {
+        Ok(Refreshed::Unchanged)
+    } else {
+        Ok(Refreshed::Changed { previous, current: info.sha256 })
+    }
+}
+
+fn fetch_and_save(target: &Target, timeouts: &Timeouts, network: &Network) -> Result<(CacheInfo, Vec<u8>), String> {
+    let protoset = descriptors::fetch_protoset(target, timeouts, network)?;
+    let info = CacheInfo { target: target.to_string(), fetched_at: now(), sha256: sha256_of(&protoset) };
+    if let Err(e) = save(target, &info, &protoset) {
+        warn!("Failed to cache descriptors: {}", e);
+    }
+    Ok((info, protoset))
+}
+
+fn load(target: &Target) -> Option<(CacheInfo, Vec<u8>)> {
+    let (info_path, protoset_path) = paths_of(target).ok()?;
+    let info: CacheInfo = serde_json::from_str(&std::fs::read_to_string(info_path).ok()?).ok()?;
+    let protoset = std::fs::read(protoset_path).ok()?;
+    // a partially written or modified cache is ignored
+    if sha256_of(&protoset) == info.sha256 { Some((info, protoset)) } else { None }
+}
+
+fn save(target: &Target, info: &CacheInfo, protoset: &[u8]) -> Result<(), String> {
+    let (info_path, protoset_path) = paths_of(target)?;
+    if let Some(dir) = info_path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create `{}`: {}", dir.display(), e))?;
+    }
+    std::fs::write(&protoset_path, protoset).map_err(|e| format!("Failed to write `{}`: {}", protoset_path.display(), e))?;
+    let json = serde_json::to_string_pretty(info).map_err(|e| e.to_string())?;
+    std::fs::write(&info_path, json).map_err(|e| format!("Failed to write `{}`: {}", info_path.display(), e))
+}
+
+fn paths_of(target: &Target) -> Result<(PathBuf, PathBuf), String> {
+    let dir = dirs::cache_dir()
+        .map(|d| d.join("zrpc-cli").join("descriptors"))
+        .ok_or_else(|| String::from("No cache directory"))?;
+    let stem = file_stem_of(target);
+    Ok((dir.join(format!("{}.json", stem)), dir.join(format!("{}.protoset", stem))))
+}
+
+fn file_stem_of(target: &Target) -> String {
+    target.to_string().chars().map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' }).collect()
+}
+
+fn is_fresh(fetched_at: u64, now: u64, ttl: Duration) -> bool {
+    now.saturating_sub(fetched_at) < ttl.as_secs()
+}
+
+fn sha256_of(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn freshness_and_file_names() {
+        assert!(is_fresh(100, 159, Duration::from_secs(60)));
+        assert!(!is_fresh(100, 160, Duration::from_secs(60)));
+        assert!(!is_fresh(100, 100, Duration::ZERO));
+        assert_eq!(file_stem_of(&Target::from("grpcs://api.internal:443").unwrap()), "grpcs___api.internal_443");
+        assert_eq!(file_stem_of(&Target::from("unix:/tmp/grpc.sock").unwrap()), "unix__tmp_grpc.sock");
+    }
+}
@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MethodDescriptor};
+use serde_json::Value;
+use crate::descriptor_cache;
+use crate::grpc_client;
+use crate::grpc_request_dsl::*;
+use crate::proxy;
+use crate::web_client;
+
+// Asks the server(through reflection) for the descriptors of all services and their transitive dependencies
+pub fn fetch_protoset(target: &Target, timeouts: &Timeouts, network: &Network) -> Result<Vec<u8>, String> {
+    match target.protocol {
+        Protocol::GrpcWeb | Protocol::GrpcWebText => return web_client::fetch_protoset(target, timeouts, network),
+        // Connect servers refuse streams over HTTP/1.1, reflection included, but speak gRPC on the same port
+        Protocol::ConnectJson | Protocol::ConnectProto => return fetch_connect_protoset(target, timeouts, network),
+        Protocol::Grpc => {}
+    }
+    let mut command = Command::new("grpcurl");
+    // grpc-go takes the proxy from the environment, so it's handed over there
+    match proxy::tunnel_for(&network.proxies, &target.host, target.port.as_ref().map_or(0, |p| p.0))? {
+        Some(tunnel) => command.env("HTTPS_PROXY", tunnel.proxy_url()).env_remove("https_proxy").env_remove("NO_PROXY").env_remove("no_proxy"),
+        None => command.env_remove("HTTPS_PROXY").env_remove("https_proxy"),
+    };
+    let args = [grpc_client::grpcurl_timeout_args(timeouts), grpc_client::grpcurl_target_args(target)].concat();
+    protoset_of_grpcurl(command, args, &target.to_string())
+}
+
+fn fetch_connect_protoset(target: &Target, timeouts: &Timeouts, network: &Network) -> Result<Vec<u8>, String> {
+    let grpc = Target { protocol: Protocol::Grpc, ..target.clone() };
+    fetch_protoset(&grpc, timeouts, network).map_err(|e| format!("{}. Descriptors of Connect servers are fetched over gRPC. \
+        For a server without gRPC reflection, give the schema as `protosets` or `protos` of a collection entry", e))
+}
+
+// `grpcurl -protoset-out <file> <args> list`, which writes the descriptors it found to the file
+fn protoset_of_grpcurl(mut command: Command, args: Vec<String>, source: &str) -> Result<Vec<u8>, String> {
+    let protoset_file = protoset_out();
+    let bytes = match command.arg("-protoset-out").arg(&protoset_file).args(args).arg("list").output() {
+        Err(e) => Err(format!("Failed to run grpcurl: {}", e)),
+        Ok(output) if !output.status.success() =>
+            Err(format!("Failed to fetch descriptors from `{}`: {}", source, String::from_utf8_lossy(&output.stderr).trim())),
+        Ok(_) => std::fs::read(&protoset_file).map_err(|e| format!("Failed to read descriptors: {}", e)),
+    };
+    let _ = std::fs::remove_file(&protoset_file);
+    bytes
+}
+
+// a file of its own for each fetch, as fetches of a process may run at once
+fn protoset_out() -> PathBuf {
+    static FETCHES: AtomicUsize = AtomicUsize::new(0);
+    std::env::temp_dir().join(format!("zrpc-cli-{}-{}.protoset", std::process::id(), FETCHES.fetch_add(1, Ordering::Relaxed)))
+}
+
+// Protoset files are read as they are, and `.proto` sources are compiled by grpcurl
+pub fn pool_from_files(schema: &SchemaFiles) -> Result<DescriptorPool, String> {
+    if !schema.protos.is_empty() {
+        if !schema.protosets.is_empty() {
+            return Err(String::from("Protoset files and `.proto` sources can't be used together"));
+        }
+        let args = schema.import_paths.iter().flat_map(|p| [String::from("-import-path"), p.clone()])
+            .chain(schema.protos.iter().flat_map(|p| [String::from("-proto"), p.clone()]))
+            .collect();
+        return pool_from(&protoset_of_grpcurl(Command::new("grpcurl"), args, &schema.protos.join(", "))?);
+    }
+    let mut pool = DescriptorPool::new();
+    for path in &schema.protosets {
+        let protoset = std::fs::read(path).map_err(|e| format!("Failed to read `{}`: {}", path, e))?;
+        pool.decode_file_descriptor_set(protoset.as_slice()).map_err(|e| format!("Invalid descriptor set `{}`: {}", path, e))?;
+    }
+    Ok(pool)
+}
+
+pub fn pool_from(protoset: &[u8]) -> Result<DescriptorPool, String> {
+    DescriptorPool::decode(protoset).map_err(|e| format!("Invalid descriptor set: {}", e))
+}
+
+pub fn find_method(pool: &DescriptorPool, service: &ServiceName, function: &ServiceFunction) -> Result<MethodDescriptor, String> {
+    let service_descriptor = pool.get_service_by_name(&service.0)
+        .ok_or_else(|| format!("Service `{}` not found in descriptors", service))?;
+    let method = service_descriptor.methods()
+        .find(|m| m.name() == function.0);
+    method.ok_or_else(|| format!("Function `{}` not found in `{}`", function, service))
+}
+
+// a protoset file when it exists, otherwise a target to fetch descriptors from
+pub fn pool_from_source(source: &str, timeouts: &Timeouts, network: &Network) -> Result<DescriptorPool, String> {
+    if std::path::Path::new(source).is_file() {
+        let protoset = std::fs::read(source).map_err(|e| format!("Failed to read `{}`: {}", source, e))?;
+        return pool_from(&protoset);
+    }
+    let target = Target::from_input(source, network.default_tls)?;
+    if target.port.is_none() && !target.host.is_unix() {
+        return Err(format!("`{}` is neither a protoset file nor a target. ex) localhost:9090", source));
+    }
+    pool_from(&fetch_protoset(&target, timeouts, network)?)
+}
+
+pub fn pool_of(target: &Target, timeouts: &Timeouts, network: &Network) -> Result<DescriptorPool, String> {
+    pool_from(&descriptor_cache::protoset_of(target, timeouts, network)?)
+}
+
+pub fn method_of(request: &ServiceRequest) -> Result<MethodDescriptor, String> {
+    let pool = match request.schema.is_empty() {
+        true => pool_of(&request.target(), &request.timeouts, &request.network)?,
+        false => pool_from_files(&request.schema)?,
+    };
+    find_method(&pool, &request.service_name, &request.service_function)
+}
+
+pub fn encode_json(message: &prost_reflect::MessageDescriptor, json: &Value) -> Result<Vec<u8>, String> {
+    let dynamic = DynamicMessage::deserialize(message.clone(), json)
+        .map_err(|e| format!("Failed to convert into `{}`: {}", message.full_name(), e))?;
+    Ok(dynamic.encode_to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_fetch_writes_its_own_file() {
+        let files = (0..3).map(|_| protoset_out()).collect::<std::collections::HashSet<PathBuf>>();
+        assert_eq!(files.len(), 3);
+    }
+}
@@ -0,0 +1,274 @@
+use prost_reflect::{DescriptorPool, EnumDescriptor, FieldDescriptor, Kind, MessageDescriptor, MethodDescriptor, ServiceDescriptor};
+use crate::body_skeleton::skeleton_of;
+use crate::descriptors;
+use crate::grpc_request_dsl::{Network, Timeouts};
+use crate::text_coloring::to_success;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DocsFormat {
+    Markdown,
+    Html,
+}
+
+impl DocsFormat {
+    pub fn from(s: &str) -> Result<DocsFormat, String> {
+        match s.to_lowercase().as_str() {
+            "markdown" | "md" => Ok(DocsFormat::Markdown),
+            "html" => Ok(DocsFormat::Html),
+            _ => Err(format!("Invalid docs format `{}`. Expected one of markdown, html", s)),
+        }
+    }
+}
+
+// A document is built once, then written as Markdown or HTML
+#[derive(Debug, PartialEq, Clone)]
+enum Inline {
+    Text(String),
+    Code(String),
+    // to the anchor of a message or an enum
+    Link(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Block {
+    Heading(usize, Option<String>, String),
+    Paragraph(Vec<Inline>),
+    Json(String),
+    Table(Vec<&'static str>, Vec<Vec<Vec<Inline>>>),
+}
+
+pub fn run(source: &str, format: DocsFormat, out: Option<String>, timeouts: Timeouts, network: &Network) -> Result<bool, String> {
+    let pool = descriptors::pool_from_source(source, &timeouts, network)?;
+    let blocks = document(&pool, source);
+    let (rendered, default_out) = match format {
+        DocsFormat::Markdown => (to_markdown(&blocks), "api.md"),
+        DocsFormat::Html => (to_html(&blocks, source), "api.html"),
+    };
+    let out = out.unwrap_or_else(|| String::from(default_out));
+    std::fs::write(&out, rendered).map_err(|e| format!("Failed to write `{}`: {}", out, e))?;
+    println!("{} {}", to_success("WROTE"), out);
+    Ok(true)
+}
+
+fn document(pool: &DescriptorPool, source: &str) -> Vec<Block> {
+    let mut blocks = vec![Block::Heading(1, None, format!("API reference of {}", source))];
+    let services: Vec<ServiceDescriptor> = pool.services().filter(|s| !s.full_name().starts_with("grpc.reflection.")).collect();
+    if !services.is_empty() {
+        blocks.push(Block::Heading(2, None, String::from("Services")));
+        services.iter().for_each(|s| service_blocks(s, &mut blocks));
+    }
+    let messages: Vec<MessageDescriptor> = pool.all_messages().filter(|m| !m.is_map_entry()).collect();
+    if !messages.is_empty() {
+        blocks.push(Block::Heading(2, None, String::from("Messages")));
+        messages.iter().for_each(|m| message_blocks(m, &mut blocks));
+    }
+    let enums: Vec<EnumDescriptor> = pool.all_enums().collect();
+    if !enums.is_empty() {
+        blocks.push(Block::Heading(2, None, String::from("Enums")));
+        enums.iter().for_each(|e| enum_blocks(e, &mut blocks));
+    }
+    blocks
+}
+
+fn service_blocks(service: &ServiceDescriptor, blocks: &mut Vec<Block>) {
+    blocks.push(Block::Heading(3, Some(service.full_name().to_string()), service.full_name().to_string()));
+    push_comment(blocks, service.parent_file().file_descriptor_proto(), service.path());
+    for method in service.methods() {
+        blocks.push(Block::Heading(4, None, method.name().to_string()));
+        push_comment(blocks, method.parent_file().file_descriptor_proto(), method.path());
+        blocks.push(Block::Paragraph(vec![
+            Inline::Code(format!("/{}/{}", service.full_name(), method.name())),
+            Inline::Text(format!(" {}", streaming_of(&method))),
+        ]));
+        blocks.push(Block::Paragraph(vec![
+            Inline::Text(String::from("Request: ")), Inline::Link(method.input().full_name().to_string()),
+            Inline::Text(String::from(", Response: ")), Inline::Link(method.output().full_name().to_string()),
+        ]));
+        let example = serde_json::to_string_pretty(&skeleton_of(&method.input())).unwrap_or_default();
+        blocks.push(Block::Json(example));
+    }
+}
+
+fn streaming_of(method: &MethodDescriptor) -> &str {
+    match (method.is_client_streaming(), method.is_server_streaming()) {
+        (false, false) => "unary",
+        (true, false) => "client streaming",
+        (false, true) => "server streaming",
+        (true, true) => "bidirectional streaming",
+    }
+}
+
+fn message_blocks(message: &MessageDescriptor, blocks: &mut Vec<Block>) {
+    blocks.push(Block::Heading(3, Some(message.full_name().to_string()), message.full_name().to_string()));
+    push_comment(blocks, message.parent_file().file_descriptor_proto(), message.path());
+    if message.fields().len() == 0 {
+        return;
+    }
+    let rows = message.fields().map(|f| vec![
+        vec![Inline::Code(f.json_name().to_string())],
+        vec![Inline::Text(f.number().to_string())],
+        type_of(&f),
+        vec![Inline::Text(comment_of(f.parent_file().file_descriptor_proto(), f.path()).unwrap_or_default())],
+    ]).collect();
+    blocks.push(Block::Table(vec!["Field", "Number", "Type", "Description"], rows));
+}
+
+fn type_of(field: &FieldDescriptor) -> Vec<Inline> {
+    let linked = |kind: &Kind| match kind {
+        Kind::Message(m) => Inline::Link(m.full_name().to_string()),
+        Kind::Enum(e) => Inline::Link(e.full_name().to_string()),
+        _ => Inline::Text(format!("{:?}", kind).to_lowercase()),
+    };
+    match field.kind() {
+        Kind::Message(entry) if field.is_map() => vec![
+            Inline::Text(String::from("map<")), linked(&entry.map_entry_key_field().kind()),
+            Inline::Text(String::from(", ")), linked(&entry.map_entry_value_field().kind()), Inline::Text(String::from(">")),
+        ],
+        kind if field.is_list() => vec![Inline::Text(String::from("repeated ")), linked(&kind)],
+        kind => {
+            let mut inlines = vec![linked(&kind)];
+            if field.containing_oneof().is_some_and(|o| !o.is_synthetic()) {
+                inlines.push(Inline::Text(format!(" (oneof {})", field.containing_oneof().map(|o| o.name().to_string()).unwrap_or_default())));
+            } else if field.supports_presence() && !matches!(kind, Kind::Message(_)) {
+                inlines.push(Inline::Text(String::from(" (optional)")));
+            }
+            inlines
+        }
+    }
+}
+
+fn enum_blocks(enumeration: &EnumDescriptor, blocks: &mut Vec<Block>) {
+    blocks.push(Block::Heading(3, Some(enumeration.full_name().to_string()), enumeration.full_name().to_string()));
+    push_comment(blocks, enumeration.parent_file().file_descriptor_proto(), enumeration.path());
+    let rows = enumeration.values().map(|v| vec![
+        vec![Inline::Code(v.name().to_string())],
+        vec![Inline::Text(v.number().to_string())],
+        vec![Inline::Text(comment_of(v.parent_file().file_descriptor_proto(), v.path()).unwrap_or_default())],
+    ]).collect();
+    blocks.push(Block::Table(vec!["Name", "Number", "Description"], rows));
+}
+
+// leading comments, when the server keeps source info
+fn comment_of(file: &prost_types::FileDescriptorProto, path: &[i32]) -> Option<String> {
+    file.source_code_info.as_ref()?
+        .location.iter()
+        .find(|l| l.path == path)
+        .and_then(|l| l.leading_comments.as_ref())
+        .map(|c| c.lines().map(|l| l.trim()).collect::<Vec<&str>>().join(" ").trim().to_string())
+        .filter(|c| !c.is_empty())
+}
+
+fn push_comment(blocks: &mut Vec<Block>, file: &prost_types::FileDescriptorProto, path: &[i32]) {
+    if let Some(comment) = comment_of(file, path) {
+        blocks.push(Block::Paragraph(vec![Inline::Text(comment)]));
+    }
+}
+
+fn anchor_of(full_name: &str) -> String {
+    full_name.replace('.', "-").to_lowercase()
+}
+
+fn to_markdown(blocks: &[Block]) -> String {
+    let inline = |inlines: &[Inline]| -> String {
+        inlines.iter().map(|i| match i {
+            // `<` would start an HTML tag
+            Inline::Text(t) => t.replace('|', "\\|").replace('<', "&lt;").replace('>', "&gt;"),
+            Inline::Code(c) => format!("`{}`", c),
+            Inline::Link(name) => format!("[{}](#{})", name, anchor_of(name)),
+        }).collect()
+    };
+    let mut out = String::new();
+    for block in blocks {
+        match block {
+            Block::Heading(level, anchor, text) => {
+                if let Some(anchor) = anchor {
+                    out.push_str(&format!("<a id=\"{}\"></a>\n\n", anchor_of(anchor)));
+                }
+                out.push_str(&format!("{} {}\n\n", "#".repeat(*level), text));
+            }
+            Block::Paragraph(inlines) => out.push_str(&format!("{}\n\n", inline(inlines))),
+            Block::Json(code) => out.push_str(&format!("```json\n{}\n```\n\n", code)),
+            Block::Table(headers, rows) => {
+                out.push_str(&format!("| {} |\n", headers.join(" | ")));
+                out.push_str(&format!("|{}\n", " --- |".repeat(headers.len())));
+                for row in rows {
+                    let cells: Vec<String> = row.iter().map(|cell| inline(cell)).collect();
+                    out.push_str(&format!("| {} |\n", cells.join(" | ")));
+                }
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+fn to_html(blocks: &[Block], title: &str) -> String {
+    let inline = |inlines: &[Inline]| -> String {
+        inlines.iter().map(|i| match i {
+            Inline::Text(t) => escape_html(t),
+            Inline::Code(c) => format!("<code>{}</code>", escape_html(c)),
+            Inline::Link(name) => format!("<a href=\"#{}\"><code>{}</code></a>", anchor_of(name), escape_html(name)),
+        }).collect()
+    };
+    let mut body = String::new();
+    for block in blocks {
+        match block {
+            Block::Heading(level, anchor, text) => {
+                let id = anchor.as_ref().map_or(String::new(), |a| format!(" id=\"{}\"", anchor_of(a)));
+                body.push_str(&format!("<h{level}{}>{}</h{level}>\n", id, escape_html(text), level = level));
+            }
+            Block::Paragraph(inlines) => body.push_str(&format!("<p>{}</p>\n", inline(inlines))),
+            Block::Json(code) => body.push_str(&format!("<pre><code>{}</code></pre>\n", escape_html(code))),
+            Block::Table(headers, rows) => {
+                body.push_str("<table>\n<tr>");
+                headers.iter().for_each(|h| body.push_str(&format!("<th>{}</th>", h)));
+                body.push_str("</tr>\n");
+                for row in rows {
+                    body.push_str("<tr>");
+                    row.iter().for_each(|cell| body.push_str(&format!("<td>{}</td>", inline(cell))));
+                    body.push_str("</tr>\n");
+                }
+                body.push_str("</table>\n");
+            }
+        }
+    }
+    format!(r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>API reference of {}</title>
+<style>
+body {{ font-family: sans-serif; max-width: 960px; margin: 2em auto; padding: 0 1em; }}
+table {{ border-collapse: collapse; }}
+th, td {{ border: 1px solid #ccc; padding: 4px 8px; text-align: left; }}
+pre {{ background: #f6f8fa; padding: 1em; }}
+</style>
+</head>
+<body>
+{}</body>
+</html>
+"#, escape_html(title), body)
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_markdown_and_html() {
+        let blocks = vec![
+            Block::Heading(3, Some(String::from("hello.Request")), String::from("hello.Request")),
+            Block::Table(vec!["Field", "Type"], vec![vec![vec![Inline::Code(String::from("labels"))], vec![
+                Inline::Text(String::from("map<string, ")), Inline::Link(String::from("hello.Label")), Inline::Text(String::from(">")),
+            ]]]),
+        ];
+        assert_eq!(to_markdown(&blocks), "<a id=\"hello-request\"></a>\n\n### hello.Request\n\n\
+            | Field | Type |\n| --- | --- |\n| `labels` | map&lt;string, [hello.Label](#hello-label)&gt; |\n\n");
+        assert!(to_html(&blocks, "localhost:9090").contains(
+            "<tr><td><code>labels</code></td><td>map&lt;string, <a href=\"#hello-label\"><code>hello.Label</code></a>&gt;</td></tr>"));
+    }
+}
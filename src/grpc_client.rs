@@ -1,98 +1,250 @@
 use crate::grpc_request_dsl;
+use crate::descriptors;
+use crate::proxy;
+use crate::web_client;
+use crate::dynamic_codec::DynamicCodec;
+use crate::grpc_status::*;
+use crate::latency::{format_duration, Timing};
+use crate::util::to_json_stream;
 
 use tonic::*;
+use tonic::codegen::http::uri::PathAndQuery;
 use grpc_request_dsl::*;
-use std::process::{Command, Output};
-
-fn output_handler(output: &Output) -> Result<Vec<String>, String> {
-    if output.status.success() {
-        let str_output = std::str::from_utf8(&output.stdout).map_err(|e| e.to_string())?;
-        Ok(str_output.split("\n").filter(|p| !p.is_empty()).map(|s| String::from(s)).collect())
-    } else {
-        let default = String::from("None");
-        let error_code = output.status.code().map_or_else(||default, |i| i.to_string());
-        match std::str::from_utf8(&output.stderr) {
-            Ok(msg) => Ok(vec![format!("Failed at code {} for reason: {}",error_code, msg)]),
-            Err(e) => Ok(vec![format!("Failed to parse err output at code {}, reason:\n{}", error_code, e)])
-        }
+use prost_reflect::{DynamicMessage, MethodDescriptor};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use hyper_util::rt::TokioIo;
+use log::warn;
+use tonic::codegen::http::Uri;
+
+pub fn grpcurl_timeout_args(timeouts: &Timeouts) -> Vec<String> {
+    let mut args = vec![String::from("-connect-timeout"), timeouts.connect.as_secs_f64().to_string()];
+    if let Some(deadline) = timeouts.deadline {
+        args.push(String::from("-max-time"));
+        args.push(deadline.as_secs_f64().to_string());
     }
+    args
 }
 
-fn parse_terminal_output<B>(output: &Output, f: &dyn Fn(&str) -> B) -> Result<Vec<B>, String> {
-    if output.status.success() {
-        // happy case
-        let str_output = std::str::from_utf8(&output.stdout).map_err(|e| e.to_string())?;
-        Ok(str_output.split("\n").filter(|p| !p.is_empty()).map(|s| f(s)).collect::<Vec<B>>())
+// grpcurl dials `unix` sockets with `-unix` and hands other targets to the gRPC resolver
+pub fn grpcurl_target_args(target: &Target) -> Vec<String> {
+    let mut args = if target.tls { vec![] } else { vec![String::from("-plaintext")] };
+    let port = target.port.as_ref().map_or(0, |p| p.0);
+    match &target.host {
+        Host::Unix(path) => args.extend([String::from("-unix"), path.clone()]),
+        Host::UnixAbstract(name) => args.extend([String::from("-unix"), format!("@{}", name)]),
+        host => args.push(format!("{}:{}", host, port)),
+    }
+    args
+}
 
-    } else {
-        let default = String::from("None");
-        let error_code = output.status.code().map_or_else(||default, |i| i.to_string());
-        match std::str::from_utf8(&output.stderr) {
-            Ok(msg) => Err(format!("Failed at code {} for reason: {}",error_code, msg)),
-            Err(e) => Err(format!("Failed to parse err output at code {}, reason: {}", error_code, e))
+pub fn request_service_list(target: &Target, timeouts: &Timeouts, network: &Network) -> Vec<ServiceName> {
+    match descriptors::pool_of(target, timeouts, network) {
+        Ok(pool) => pool.services().map(|s| ServiceName::from(s.full_name())).collect(),
+        Err(e) => {
+            eprintln!("Failed to list services: {}", e);
+            vec![]
         }
     }
 }
 
-pub fn request_service_list(host: &Host, port: &Port) -> Vec<ServiceName> {
-    let command = Command::new("grpcurl")
-        .arg("-plaintext")
-        .arg(format!("{}:{}", host.0, port.0))
-        .arg("list")
-        .output();
+pub fn request_function_list_by(request: &ServiceRequest) -> Vec<ServiceFunction> {
+    request_function_list(&request.target(), &request.service_name, &request.timeouts, &request.network)
+}
 
-    match command {
-        Ok(output) => match parse_terminal_output(&output, &|s| ServiceName(String::from(s))) {
-            Ok(services) => services,
-            Err(e) => {
-                eprintln!("Terminal parsing error: {}", e);
-                vec![]
-            }
-        },
+pub fn request_function_list(target: &Target, service: &ServiceName, timeouts: &Timeouts, network: &Network) -> Vec<ServiceFunction> {
+    let functions = descriptors::pool_of(target, timeouts, network)
+        .and_then(|pool| pool.get_service_by_name(&service.0).ok_or_else(|| format!("Service `{}` not found", service)))
+        .map(|s| s.methods().map(|m| ServiceFunction::from(&format!("{}.{}", service, m.name()))).collect());
+    match functions {
+        Ok(functions) => functions,
         Err(e) => {
-            eprintln!("Terminal parsing error: {}", e);
+            eprintln!("Failed to list functions: {}", e);
             vec![]
         }
     }
 }
-pub fn request_function_list_by(request: &ServiceRequest) -> Vec<ServiceFunction> {
-    request_function_list(&request.host, &request.port, &request.service_name)
+
+pub fn runtime() -> &'static tokio::runtime::Runtime {
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| tokio::runtime::Runtime::new().expect("Failed to start async runtime"))
+}
+
+pub fn encode_body(method: &MethodDescriptor, body: &str) -> Result<Vec<DynamicMessage>, String> {
+    to_json_stream(body)?.iter()
+        .map(|b| DynamicMessage::deserialize(method.input(), b).map_err(|e| format!("Invalid request body for `{}`: {}", method.input().full_name(), e)))
+        .collect()
+}
+
+
+pub fn path_of(service_request: &ServiceRequest) -> Result<PathAndQuery, String> {
+    PathAndQuery::from_maybe_shared(format!("/{}/{}", service_request.service_name, service_request.service_function))
+        .map_err(|e| format!("Invalid function path: {}", e))
+}
+
+pub async fn connect(target: &Target, timeouts: &Timeouts, network: &Network) -> Result<transport::Channel, tonic::Status> {
+    if target.protocol != Protocol::Grpc {
+        return Err(tonic::Status::invalid_argument(format!("Only gRPC is supported here, not {}", target.protocol)));
+    }
+    let port = target.port.as_ref().map_or(0, |p| p.0);
+    let scheme = if target.tls { "https" } else { "http" };
+    let address = match &target.host {
+        Host::Name(name) => format!("{}://{}:{}", scheme, name, port),
+        Host::Ipv6(address) => format!("{}://[{}]:{}", scheme, address, port),
+        // resolved by the system resolver
+        Host::Dns { name } => format!("{}://{}:{}", scheme, name, port),
+        // the authority of a unix socket is not used for dialing
+        Host::Unix(_) | Host::UnixAbstract(_) => String::from("http://localhost"),
+    };
+    let mut endpoint = transport::Endpoint::from_shared(address)
+        .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?
+        .connect_timeout(timeouts.connect);
+    if target.tls {
+        endpoint = endpoint.tls_config(transport::ClientTlsConfig::new().with_native_roots())
+            .map_err(|e| tonic::Status::invalid_argument(format!("Invalid TLS config: {}", e)))?;
+    }
+    let tunnel = proxy::tunnel_for(&network.proxies, &target.host, port).map_err(tonic::Status::invalid_argument)?;
+    let connect_timeout = timeouts.connect;
+    let connected = match (target.host.clone(), tunnel) {
+        (_, Some(tunnel)) => endpoint.connect_with_connector(tower::service_fn(move |_: Uri| {
+            let tunnel = tunnel.clone();
+            async move { tunnel.open(connect_timeout).await.map(TokioIo::new) }
+        })).await,
+        (Host::Unix(path), _) => endpoint.connect_with_connector(tower::service_fn(move |_: Uri| {
+            let path = path.clone();
+            async move { tokio::net::UnixStream::connect(path).await.map(TokioIo::new) }
+        })).await,
+        (Host::UnixAbstract(name), _) => endpoint.connect_with_connector(tower::service_fn(move |_: Uri| {
+            let name = name.clone();
+            async move { connect_abstract(&name).map(TokioIo::new) }
+        })).await,
+        _ => endpoint.connect().await,
+    };
+    connected.map_err(|e| tonic::Status::unavailable(format!("Failed to connect to `{}`: {}", target, with_sources(&e))))
 }
 
+// tonic's transport errors tell little without what caused them
+fn with_sources(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        // io errors repeat the error they wrap
+        let cause = error.to_string();
+        if !message.ends_with(&cause) {
+            message.push_str(&format!(": {}", cause));
+        }
+        source = error.source();
+    }
+    message
+}
+
+#[cfg(target_os = "linux")]
+fn connect_abstract(name: &str) -> std::io::Result<tokio::net::UnixStream> {
+    use std::os::linux::net::SocketAddrExt;
+    let address = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())?;
+    let stream = std::os::unix::net::UnixStream::connect_addr(&address)?;
+    stream.set_nonblocking(true)?;
+    tokio::net::UnixStream::from_std(stream)
+}
 
-pub fn request_function_list(host: &Host, port: &Port, service: &ServiceName) -> Vec<ServiceFunction> {
-    let command = Command::new("grpcurl")
-        .arg("-plaintext")
-        .arg(format!("{}:{}", host.0, port.0))
-        .arg("list")
-        .arg(format!("{}", service.0))
-        .output();
+#[cfg(not(target_os = "linux"))]
+fn connect_abstract(_name: &str) -> std::io::Result<tokio::net::UnixStream> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "Abstract unix sockets are only available on Linux"))
+}
 
-    match command {
-        Ok(output) => match parse_terminal_output(&output, &|s| ServiceFunction(String::from(s))) {
-            Ok(services) => services,
-            Err(e) => {
-                eprintln!("Terminal parsing error: {}", e);
-                vec![]
+pub fn request(service_request: &ServiceRequest) -> Result<GrpcResponse, String> {
+    let method = descriptors::method_of(service_request)?;
+    let messages = encode_body(&method, &service_request.body.0)?;
+    let path = path_of(service_request)?;
+    let policy = &service_request.retry_policy;
+    let target = service_request.target();
+    Ok(runtime().block_on(async {
+        let mut attempt = 1;
+        loop {
+            let response = match target.protocol {
+                Protocol::Grpc => call(&target, path.clone(), DynamicCodec::new(method.output()), messages.clone(), &service_request.metadata, service_request.timeouts, &service_request.network).await,
+                _ => web_client::call(&target, path.path(), &method, &messages, &service_request.metadata, service_request.timeouts, &service_request.network).await,
+            };
+            if !policy.should_retry(&response.status, attempt) {
+                return GrpcResponse { attempts: attempt, ..response };
             }
-        },
-        Err(e) => {
-            eprintln!("Terminal parsing error: {}", e);
-            vec![]
+            let delay = policy.backoff(attempt, &response.status, service_request.timeouts.deadline);
+            warn!("Attempt {}/{} failed with {}, retrying in {}", attempt, policy.max_attempts, response.status.code, format_duration(delay));
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }))
+}
+
+pub fn new_request<T>(message: T, timeouts: &Timeouts) -> Request<T> {
+    let mut request = Request::new(message);
+    if let Some(deadline) = timeouts.deadline {
+        request.set_timeout(deadline);
+    }
+    request
+}
+
+// Keys ending with `-bin` take base64 values, as gRPC sends them
+pub fn add_metadata(to: &mut metadata::MetadataMap, metadata: &BTreeMap<String, String>) -> Result<(), tonic::Status> {
+    for (key, value) in metadata {
+        let invalid = |e: String| tonic::Status::invalid_argument(format!("Invalid metadata `{}`: {}", key, e));
+        if key.ends_with("-bin") {
+            let bytes = STANDARD.decode(value).map_err(|e| invalid(e.to_string()))?;
+            let key = metadata::BinaryMetadataKey::from_bytes(key.as_bytes()).map_err(|e| invalid(e.to_string()))?;
+            to.append_bin(key, metadata::BinaryMetadataValue::from_bytes(&bytes));
+        } else {
+            let key = metadata::AsciiMetadataKey::from_bytes(key.as_bytes()).map_err(|e| invalid(e.to_string()))?;
+            let value = metadata::AsciiMetadataValue::try_from(value.as_str()).map_err(|e| invalid(e.to_string()))?;
+            to.append(key, value);
         }
     }
+    Ok(())
+}
+
+// the deadline is also enforced locally, in case the server ignores `grpc-timeout`
+pub async fn with_deadline<T>(deadline: Option<Duration>, f: impl Future<Output = Result<T, tonic::Status>>) -> Result<T, tonic::Status> {
+    match deadline {
+        Some(d) => tokio::time::timeout(d, f).await
+            .unwrap_or_else(|_| Err(tonic::Status::deadline_exceeded(format!("Deadline of {:?} exceeded", d)))),
+        None => f.await,
+    }
 }
 
-pub fn request(service_request: &ServiceRequest) -> Result<String, String> {
-    let s = service_request;
-    let command = Command::new("grpcurl")
-        .arg("-plaintext")
-        .arg("-d")
-        .arg(format!("{}", s.body))
-        .arg(format!("{}:{}", s.host, s.port))
-        .arg(format!("{}/{}", s.service_name, s.service_function))
-        .output();
+async fn call(target: &Target, path: PathAndQuery, codec: DynamicCodec, messages: Vec<DynamicMessage>, metadata: &BTreeMap<String, String>, timeouts: Timeouts, network: &Network) -> GrpcResponse {
+    let mut response = GrpcResponse { status: GrpcStatus::ok(), headers: vec![], trailers: vec![], messages: vec![], timing: Timing::default(), attempts: 1 };
+    let started = Instant::now();
+    let result: Result<(), tonic::Status> = with_deadline(timeouts.deadline, async {
+        let channel = connect(target, &timeouts, network).await?;
+        response.timing.connect = started.elapsed();
+        let mut grpc = client::Grpc::new(channel);
+        grpc.ready().await.map_err(|e| tonic::Status::unavailable(e.to_string()))?;
+        let mut request = new_request(tokio_stream::iter(messages), &timeouts);
+        add_metadata(request.metadata_mut(), metadata)?;
+        let streaming = grpc.streaming(request, path, codec).await?;
+        response.headers = metadata_pairs(streaming.metadata());
+        let mut stream = streaming.into_inner();
+        let mut last_arrival: Option<Instant> = None;
+        while let Some(message) = stream.message().await? {
+            let arrival = Instant::now();
+            match last_arrival {
+                Some(previous) => response.timing.inter_arrival.push(arrival - previous),
+                None => response.timing.first_message = Some(arrival - started),
+            }
+            last_arrival = Some(arrival);
+            response.messages.push(serde_json::to_value(&message).map_err(|e| tonic::Status::internal(e.to_string()))?);
+        }
+        response.trailers = stream.trailers().await?.map(|t| metadata_pairs(&t)).unwrap_or_default();
+        Ok(())
+    }).await;
+    response.timing.total = started.elapsed();
 
-    let output = command.map_err(|e| e.to_string())?;
-    output_handler(&output).map(|s| s.join("\n"))
+    if let Err(status) = result {
+        response.status = GrpcStatus::from(&status);
+        response.trailers = metadata_pairs(status.metadata());
+    }
+    response
 }
@@ -1,27 +1,207 @@
+use std::collections::BTreeMap;
 use std::fmt::Formatter;
+use std::net::Ipv6Addr;
+use std::time::Duration;
+use serde::{Deserialize, Deserializer, Serialize};
+use crate::proxy::ProxyRule;
+use crate::retry::RetryPolicy;
+use crate::util::{deserialize_duration, deserialize_parsed};
 
-#[derive(Debug, Clone)]
-pub struct Host(pub String);
+#[derive(Debug, Clone, PartialEq)]
+pub enum Host {
+    // host name or IPv4 address
+    Name(String),
+    Ipv6(Ipv6Addr),
+    // `dns:///name`, resolved by the system resolver
+    Dns { name: String },
+    Unix(String),
+    UnixAbstract(String),
+}
 
 impl Host {
     pub fn from(s: String) -> Result<Host, String> {
-        if s.contains(":") {
+        let s = s.trim();
+        if let Some(name) = s.strip_prefix("unix-abstract:") {
+            if name.is_empty() { Err(String::from("Invalid format. empty socket name")) } else { Ok(Host::UnixAbstract(String::from(name))) }
+        } else if let Some(path) = s.strip_prefix("unix:") {
+            // `unix:///tmp/a.sock` is the same as `unix:/tmp/a.sock`
+            let path = path.strip_prefix("//").unwrap_or(path);
+            if path.is_empty() { Err(String::from("Invalid format. empty socket path")) } else { Ok(Host::Unix(String::from(path))) }
+        } else if let Some(rest) = s.strip_prefix("dns:") {
+            match Host::from(String::from(dns_name(rest)?))? {
+                Host::Name(name) => Ok(Host::Dns { name }),
+                other => Err(format!("Invalid name to resolve `{}`", other)),
+            }
+        } else if let Some(address) = s.strip_prefix('[').and_then(|a| a.strip_suffix(']')) {
+            address.parse::<Ipv6Addr>().map(Host::Ipv6).map_err(|e| format!("Invalid IPv6 address `{}`: {}", address, e))
+        } else if let Ok(address) = s.parse::<Ipv6Addr>() {
+            Ok(Host::Ipv6(address))
+        } else if s.contains(":") {
             Err(String::from("Invalid format. contains `:`"))
         } else if s.contains("/") {
             Err(String::from("Invalid format. contains `/`"))
+        } else if s.is_empty() {
+            Err(String::from("Invalid format. empty host"))
         } else {
-            Ok(Host(s))
+            Ok(Host::Name(String::from(s)))
         }
     }
+
+    pub fn is_unix(&self) -> bool {
+        matches!(self, Host::Unix(_) | Host::UnixAbstract(_))
+    }
+}
+
+// `///name` or `name`. `//authority/name` asks a given DNS server, which isn't supported
+fn dns_name(s: &str) -> Result<&str, String> {
+    match s.strip_prefix("//") {
+        Some(rest) => match rest.split_once('/') {
+            Some(("", name)) => Ok(name),
+            Some((authority, name)) => Err(format!("Resolving with a given DNS server `{}` is not supported. Use `dns:///{}` for the system resolver", authority, name)),
+            None => Err(format!("Invalid dns target `dns:{}`. ex) dns:///example.com:443", s)),
+        },
+        None => Ok(s),
+    }
 }
 
 impl std::fmt::Display for Host {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        match self {
+            Host::Name(name) => write!(f, "{}", name),
+            Host::Ipv6(address) => write!(f, "[{}]", address),
+            Host::Dns { name } => write!(f, "dns:///{}", name),
+            Host::Unix(path) => write!(f, "unix:{}", path),
+            Host::UnixAbstract(name) => write!(f, "unix-abstract:{}", name),
+        }
+    }
+}
+
+// How calls are carried. gRPC-Web and Connect also work over HTTP/1.1, such as behind Envoy
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+    Grpc,
+    GrpcWeb,
+    GrpcWebText,
+    ConnectJson,
+    ConnectProto,
+}
+
+impl Protocol {
+    pub fn from(s: &str) -> Result<Protocol, String> {
+        match s {
+            "grpc" => Ok(Protocol::Grpc),
+            "grpc-web" => Ok(Protocol::GrpcWeb),
+            "grpc-web-text" => Ok(Protocol::GrpcWebText),
+            "connect" | "connect+json" => Ok(Protocol::ConnectJson),
+            "connect+proto" => Ok(Protocol::ConnectProto),
+            _ => Err(format!("Unknown protocol `{}`. Expected one of grpc, grpc-web, grpc-web-text, connect, connect+proto", s)),
+        }
     }
 }
 
-#[derive(Debug, Clone)]
+impl std::fmt::Display for Protocol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Protocol::Grpc => write!(f, "grpc"),
+            Protocol::GrpcWeb => write!(f, "grpc-web"),
+            Protocol::GrpcWebText => write!(f, "grpc-web-text"),
+            Protocol::ConnectJson => write!(f, "connect"),
+            Protocol::ConnectProto => write!(f, "connect+proto"),
+        }
+    }
+}
+
+// Where to dial. The port is absent for unix domain sockets, or when not typed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    pub host: Host,
+    pub port: Option<Port>,
+    pub tls: bool,
+    pub protocol: Protocol,
+}
+
+impl Target {
+    // `host:port`, `[::1]:port`, `dns:///name:port`, `unix:/path` or `unix-abstract:name`
+    // A URL such as `grpcs://host:port`(TLS) or `http://host:port`(plaintext) is also accepted,
+    // and the protocol other than gRPC goes before the scheme. ex) `grpc-web+https://host:port`, `connect+http://host:port`
+    // Plaintext unless the scheme tells TLS
+    pub fn from(s: &str) -> Result<Target, String> {
+        let s = s.trim();
+        if let Some((scheme, rest)) = s.split_once("://") {
+            let (protocol, transport) = match scheme.rsplit_once('+') {
+                Some((protocol, transport)) => (Protocol::from(protocol)?, transport),
+                None => (Protocol::Grpc, scheme),
+            };
+            let tls = match transport {
+                "grpcs" | "https" => Some(true),
+                "grpc" | "http" => Some(false),
+                _ if protocol != Protocol::Grpc => return Err(format!("Invalid target `{}`. ex) {}+https://host:port", s, protocol)),
+                _ => None,
+            };
+            if let Some(tls) = tls {
+                let target = Target::from(rest.trim_end_matches('/'))?;
+                if target.host.is_unix() || target.tls || target.protocol != Protocol::Grpc {
+                    return Err(format!("Invalid target `{}`", s));
+                }
+                // the port of the scheme, as a browser would. `grpc://` has none
+                let default_port = match transport {
+                    "grpcs" | "https" => Some(Port(443)),
+                    "http" => Some(Port(80)),
+                    _ => None,
+                };
+                return Ok(Target { port: target.port.or(default_port), tls, protocol, ..target });
+            }
+        }
+        if s.starts_with("unix:") || s.starts_with("unix-abstract:") {
+            return Ok(Target { host: Host::from(String::from(s))?, port: None, tls: false, protocol: Protocol::Grpc });
+        }
+        if let Some(rest) = s.strip_prefix("dns:") {
+            let target = Target::from(dns_name(rest)?)?;
+            return match target.host {
+                Host::Name(name) => Ok(Target { host: Host::Dns { name }, ..target }),
+                other => Err(format!("Invalid name to resolve `{}`", other)),
+            };
+        }
+        let (host, port) = match s.rsplit_once(':') {
+            // `[::1]:port` or `host:port`, but not a bare IPv6 address such as `::1`
+            Some((host, port)) if host.ends_with(']') || !host.contains(':') => (host, Some(Port::from(String::from(port))?)),
+            _ => (s, None),
+        };
+        Ok(Target { host: Host::from(String::from(host))?, port, tls: false, protocol: Protocol::Grpc })
+    }
+
+    // as typed by a user, where a target without a scheme telling TLS or plaintext takes `default_tls`(`defaults.tls` of settings)
+    pub fn from_input(s: &str, default_tls: bool) -> Result<Target, String> {
+        let target = Target::from(s)?;
+        match Target::tells_tls(s) || target.host.is_unix() {
+            true => Ok(target),
+            false => Ok(Target { tls: default_tls, ..target }),
+        }
+    }
+
+    fn tells_tls(s: &str) -> bool {
+        s.trim().split_once("://")
+            .is_some_and(|(scheme, _)| matches!(scheme.rsplit('+').next(), Some("grpcs" | "https" | "grpc" | "http")))
+    }
+}
+
+// Plaintext is written out, so a target reads back the same with `defaults.tls` on
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (self.protocol, self.tls) {
+            (Protocol::Grpc, _) if self.host.is_unix() => {}
+            (Protocol::Grpc, true) => write!(f, "grpcs://")?,
+            (Protocol::Grpc, false) => write!(f, "grpc://")?,
+            (protocol, tls) => write!(f, "{}+{}://", protocol, if tls { "https" } else { "http" })?,
+        }
+        match &self.port {
+            Some(port) if !self.host.is_unix() => write!(f, "{}:{}", self.host, port),
+            _ => write!(f, "{}", self.host),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct Port(pub u16);
 
 impl Port {
@@ -39,7 +219,7 @@ impl std::fmt::Display for Port {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RequestBody(pub String);
 
 impl RequestBody {
@@ -54,7 +234,7 @@ impl std::fmt::Display for RequestBody {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ServiceFunction(pub String);
 
 impl ServiceFunction {
@@ -67,7 +247,7 @@ impl std::fmt::Display for ServiceFunction {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ServiceName(pub String);
 
 impl ServiceName {
@@ -80,13 +260,90 @@ impl std::fmt::Display for ServiceName {
     }
 }
 
-#[derive(Debug, Clone)]
+// `[timeouts]` of settings
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Timeouts {
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub connect: Duration,
+    #[serde(deserialize_with = "deserialize_deadline")]
+    pub deadline: Option<Duration>,
+}
+
+impl Timeouts {
+    pub fn default() -> Timeouts {
+        Timeouts { connect: Duration::from_secs(10), deadline: Some(Duration::from_secs(30)) }
+    }
+
+    // `0` or `none` means no deadline
+    pub fn parse_deadline(s: &str) -> Result<Option<Duration>, String> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "0" | "none" => Ok(None),
+            other => crate::util::parse_duration(other).map(Some),
+        }
+    }
+}
+
+fn deserialize_deadline<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    deserialize_parsed(deserializer, Timeouts::parse_deadline)
+}
+
+impl std::fmt::Display for Timeouts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let deadline = self.deadline.map_or(String::from("none"), |d| format!("{:?}", d));
+        write!(f, "deadline {}, connect timeout {:?}", deadline, self.connect)
+    }
+}
+
+// How targets are reached, from settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct Network {
+    // whether a target typed without a scheme uses TLS
+    pub default_tls: bool,
+    pub proxies: Vec<ProxyRule>,
+    // how long descriptors fetched by reflection are used without fetching them again
+    pub descriptor_cache_ttl: Duration,
+}
+
+impl Network {
+    pub fn default() -> Network {
+        Network { default_tls: false, proxies: vec![], descriptor_cache_ttl: Duration::from_secs(600) }
+    }
+}
+
+// descriptors read from files rather than fetched by reflection, as grpcurl's `-protoset`, `-proto` and `-import-path`
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct SchemaFiles {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub protosets: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub protos: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub import_paths: Vec<String>,
+}
+
+impl SchemaFiles {
+    pub fn is_empty(&self) -> bool {
+        self.protosets.is_empty() && self.protos.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct ServiceRequest {
     pub host: Host,
     pub port: Port,
     pub service_name: ServiceName,
     pub service_function: ServiceFunction,
     pub body: RequestBody,
+    pub tls: bool,
+    pub protocol: Protocol,
+    pub timeouts: Timeouts,
+    pub retry_policy: RetryPolicy,
+    pub network: Network,
+    // sent along with every call. Keys ending with `-bin` take base64 values
+    pub metadata: BTreeMap<String, String>,
+    // reflection on the target when empty
+    pub schema: SchemaFiles,
 }
 
 impl ServiceRequest {
@@ -95,6 +352,33 @@ impl ServiceRequest {
     }
 
 
+    // `localhost:9090`(or any form of `Target`) and `helloworld.Greeter/SayHello`(or `helloworld.Greeter.SayHello`), as typed
+    pub fn parse(target: &str, method: &str, network: Network) -> Result<ServiceRequest, String> {
+        let target = Target::from_input(target, network.default_tls)?;
+        let (service, function) = ServiceRequest::parse_method(method)?;
+        if target.port.is_none() && !target.host.is_unix() {
+            return Err(format!("Missing port in `{}`. ex) localhost:9090", target));
+        }
+        let mut request = ServiceRequest::new(target.host.clone());
+        request.update_target(target);
+        request.update_service(service);
+        request.update_function(function);
+        request.update_network(network);
+        Ok(request)
+    }
+
+    // `helloworld.Greeter/SayHello` or `helloworld.Greeter.SayHello`
+    pub fn parse_method(method: &str) -> Result<(ServiceName, ServiceFunction), String> {
+        let (service, function) = method.split_once('/')
+            .or_else(|| method.rsplit_once('.'))
+            .ok_or_else(|| format!("Invalid method `{}`. ex) helloworld.Greeter/SayHello", method))?;
+        Ok((ServiceName::from(service), ServiceFunction::from(function)))
+    }
+
+    pub fn target(&self) -> Target {
+        Target { host: self.host.clone(), port: Some(self.port.clone()), tls: self.tls, protocol: self.protocol }
+    }
+
     pub fn new(host: Host) -> ServiceRequest {
         ServiceRequest {
             host,
@@ -102,17 +386,30 @@ impl ServiceRequest {
             service_name: ServiceName::from(""),
             service_function: ServiceFunction::from(""),
             body: RequestBody::from("{}"),
+            tls: false,
+            protocol: Protocol::Grpc,
+            timeouts: Timeouts::default(),
+            retry_policy: RetryPolicy::none(),
+            network: Network::default(),
+            metadata: BTreeMap::new(),
+            schema: SchemaFiles::default(),
         }
     }
 
-    pub fn update_host(&mut self, host: Host) {
-        self.host = host;
-    }
-
     pub fn update_port(&mut self, port: Port) {
         self.port = port;
     }
 
+    // host, protocol, and port and TLS when the target tells
+    pub fn update_target(&mut self, target: Target) {
+        self.host = target.host;
+        self.tls = target.tls;
+        self.protocol = target.protocol;
+        if let Some(port) = target.port {
+            self.port = port;
+        }
+    }
+
     pub fn update_service(&mut self, service_name: ServiceName) {
         self.service_name = service_name;
     }
@@ -132,4 +429,94 @@ impl ServiceRequest {
     pub fn update_body(&mut self, body: String) {
         self.body = RequestBody(body)
     }
+
+    pub fn update_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts
+    }
+
+    pub fn update_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy
+    }
+
+    pub fn update_network(&mut self, network: Network) {
+        self.network = network
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(s: &str) -> Target {
+        Target::from(s).unwrap()
+    }
+
+    #[test]
+    fn parse_tcp_targets() {
+        assert_eq!(target("localhost:9090"), Target { host: Host::Name(String::from("localhost")), port: Some(Port(9090)), tls: false, protocol: Protocol::Grpc });
+        assert_eq!(target("10.0.0.1"), Target { host: Host::Name(String::from("10.0.0.1")), port: None, tls: false, protocol: Protocol::Grpc });
+        assert_eq!(target("[::1]:50051"), Target { host: Host::Ipv6(Ipv6Addr::LOCALHOST), port: Some(Port(50051)), tls: false, protocol: Protocol::Grpc });
+        assert_eq!(target("::1"), Target { host: Host::Ipv6(Ipv6Addr::LOCALHOST), port: None, tls: false, protocol: Protocol::Grpc });
+        assert_eq!(target("[::1]:50051").to_string(), "grpc://[::1]:50051");
+        assert!(Target::from("localhost:http").is_err());
+        assert!(Target::from("local/host:9090").is_err());
+    }
+
+    #[test]
+    fn parse_dns_targets() {
+        let name = String::from("api.internal");
+        assert_eq!(target("dns:///api.internal:443"), Target { host: Host::Dns { name }, port: Some(Port(443)), tls: false, protocol: Protocol::Grpc });
+        assert!(Target::from("dns://8.8.8.8/api.internal:443").unwrap_err().starts_with("Resolving with a given DNS server `8.8.8.8` is not supported"));
+        assert_eq!(target("dns:///api.internal:443").to_string(), "grpc://dns:///api.internal:443");
+        assert!(Target::from("dns://api.internal").is_err());
+    }
+
+    #[test]
+    fn parse_unix_targets() {
+        assert_eq!(target("unix:/tmp/grpc.sock"), Target { host: Host::Unix(String::from("/tmp/grpc.sock")), port: None, tls: false, protocol: Protocol::Grpc });
+        assert_eq!(target("unix:///tmp/grpc.sock").host, Host::Unix(String::from("/tmp/grpc.sock")));
+        assert_eq!(target("unix:relative.sock").host, Host::Unix(String::from("relative.sock")));
+        assert_eq!(target("unix-abstract:grpc").host, Host::UnixAbstract(String::from("grpc")));
+        assert!(Target::from("unix:").is_err());
+    }
+
+    #[test]
+    fn parse_url_targets() {
+        assert_eq!(target("grpcs://api:443"), Target { host: Host::Name(String::from("api")), port: Some(Port(443)), tls: true, protocol: Protocol::Grpc });
+        assert_eq!(target("http://localhost:50051/"), Target { host: Host::Name(String::from("localhost")), port: Some(Port(50051)), tls: false, protocol: Protocol::Grpc });
+        assert_eq!(target("https://[::1]:8443").to_string(), "grpcs://[::1]:8443");
+        assert!(Target::from("grpcs://unix:/tmp/grpc.sock").is_err());
+        assert!(Target::from("ftp://api:21").is_err());
+        assert_eq!((target("grpcs://api").port, target("https://api/").port), (Some(Port(443)), Some(Port(443))));
+        assert_eq!((target("http://api").port, target("grpc://api").port), (Some(Port(80)), None));
+    }
+
+    #[test]
+    fn parse_protocols() {
+        let web = target("grpc-web+https://envoy:8443");
+        assert_eq!((web.protocol, web.tls, web.port), (Protocol::GrpcWeb, true, Some(Port(8443))));
+        assert_eq!(target("connect+proto+http://localhost:8080").protocol, Protocol::ConnectProto);
+        assert_eq!(target("connect+http://localhost:8080").to_string(), "connect+http://localhost:8080");
+        assert_eq!(target("grpc-web-text+http://localhost:8080").to_string(), "grpc-web-text+http://localhost:8080");
+        assert!(Target::from("grpc-web+unix:/tmp/grpc.sock").is_err());
+        assert!(Target::from("soap+http://localhost:8080").is_err());
+    }
+
+    #[test]
+    fn read_back_with_default_tls() {
+        let targets = ["localhost:9090", "grpcs://api:443", "[::1]:50051", "dns:///api.internal:443", "unix:/tmp/grpc.sock", "unix-abstract:grpc", "grpc-web+http://envoy:8080"];
+        for s in targets {
+            assert_eq!(Target::from_input(&target(s).to_string(), true), Ok(target(s)), "{}", s);
+        }
+        assert!(Target::from_input("api:9090", true).unwrap().tls);
+        assert!(!Target::from_input("grpc://api:9090", true).unwrap().tls);
+    }
+
+    #[test]
+    fn parse_service_request() {
+        let request = ServiceRequest::parse("unix:/tmp/grpc.sock", "helloworld.Greeter/SayHello", Network::default()).unwrap();
+        assert_eq!(request.host, Host::Unix(String::from("/tmp/grpc.sock")));
+        assert_eq!(request.service_function, ServiceFunction::from("SayHello"));
+        assert!(ServiceRequest::parse("localhost", "helloworld.Greeter/SayHello", Network::default()).is_err());
+    }
 }
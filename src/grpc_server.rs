@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use hyper::body::Incoming;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use prost_reflect::{DescriptorPool, DynamicMessage, MethodDescriptor};
+use tonic::body::Body;
+use tonic::codegen::http;
+use tonic::server::StreamingService;
+use tonic::Status;
+use tower::ServiceExt;
+use crate::dynamic_codec::DynamicCodec;
+use crate::grpc_client;
+use crate::text_coloring::to_error;
+
+// `/package.Service/Method`, the path a method is called with
+pub fn path_of(method: &MethodDescriptor) -> String {
+    format!("/{}/{}", method.parent_service().full_name(), method.name())
+}
+
+// `:9091` listens on every interface, like Go's `net.Listen`
+pub fn parse_listen(listen: &str) -> Result<(String, u16), String> {
+    let (host, port) = listen.rsplit_once(':').ok_or_else(|| format!("Invalid listen address `{}`. ex) :9091, 127.0.0.1:9091", listen))?;
+    let port = port.parse::<u16>().map_err(|_| format!("Invalid port `{}`", port))?;
+    let host = match host.trim_start_matches('[').trim_end_matches(']') {
+        "" => String::from("0.0.0.0"),
+        host => String::from(host),
+    };
+    Ok((host, port))
+}
+
+// Serves every method of `pool` with the service `service_of` makes for it, along with server reflection of `pool`.
+// Runs until the process is stopped
+pub fn serve<F, S>(host: &str, port: u16, pool: &DescriptorPool, service_of: F) -> Result<bool, String>
+where
+    F: Fn(MethodDescriptor) -> S + Send + Sync + 'static,
+    S: StreamingService<DynamicMessage, Response = DynamicMessage> + Send + 'static,
+    S::Future: Send + 'static,
+    S::ResponseStream: Send + 'static,
+{
+    let methods: HashMap<String, MethodDescriptor> = methods_of(pool).into_iter().map(|m| (path_of(&m), m)).collect();
+
+    // served as it is, so clients can discover the services
+    let files = prost_types::FileDescriptorSet {
+        file: pool.file_descriptor_protos().filter(|f| !f.package().starts_with("grpc.reflection.")).cloned().collect(),
+    };
+    let reflection_v1 = tonic_reflection::server::Builder::configure()
+        .register_file_descriptor_set(files.clone())
+        .build_v1()
+        .map_err(|e| format!("Failed to serve reflection: {}", e))?;
+    let reflection_v1alpha = tonic_reflection::server::Builder::configure()
+        .register_file_descriptor_set(files)
+        .build_v1alpha()
+        .map_err(|e| format!("Failed to serve reflection: {}", e))?;
+
+    let methods = Arc::new(methods);
+    let service_of = Arc::new(service_of);
+    grpc_client::runtime().block_on(async move {
+        let listener = tokio::net::TcpListener::bind((host, port)).await
+            .map_err(|e| format!("Failed to listen on {}:{}: {}", host, port, e))?;
+        loop {
+            let (stream, _) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    eprintln!("{}", to_error(&format!("Failed to accept a connection: {}", e)));
+                    continue;
+                }
+            };
+            let (methods, service_of) = (methods.clone(), service_of.clone());
+            let (reflection_v1, reflection_v1alpha) = (reflection_v1.clone(), reflection_v1alpha.clone());
+            let service = hyper::service::service_fn(move |request: http::Request<Incoming>| {
+                let (methods, service_of) = (methods.clone(), service_of.clone());
+                let (reflection_v1, reflection_v1alpha) = (reflection_v1.clone(), reflection_v1alpha.clone());
+                async move {
+                    let path = request.uri().path();
+                    if path.starts_with("/grpc.reflection.v1.ServerReflection/") {
+                        reflection_v1.oneshot(request).await
+                    } else if path.starts_with("/grpc.reflection.v1alpha.ServerReflection/") {
+                        reflection_v1alpha.oneshot(request).await
+                    } else {
+                        Ok::<_, Infallible>(serve_method(&methods, service_of.as_ref(), request).await)
+                    }
+                }
+            });
+            tokio::spawn(async move {
+                if let Err(e) = hyper::server::conn::http2::Builder::new(TokioExecutor::new())
+                    .serve_connection(TokioIo::new(stream), service).await {
+                    eprintln!("{}", to_error(&format!("Connection closed: {}", e)));
+                }
+            });
+        }
+    })
+}
+
+pub fn methods_of(pool: &DescriptorPool) -> Vec<MethodDescriptor> {
+    pool.services()
+        .filter(|s| !s.full_name().starts_with("grpc.reflection."))
+        .flat_map(|s| s.methods().collect::<Vec<_>>())
+        .collect()
+}
+
+async fn serve_method<F, S>(methods: &HashMap<String, MethodDescriptor>, service_of: &F, request: http::Request<Incoming>) -> http::Response<Body>
+where
+    F: Fn(MethodDescriptor) -> S,
+    S: StreamingService<DynamicMessage, Response = DynamicMessage> + Send,
+    S::ResponseStream: Send + 'static,
+{
+    match methods.get(request.uri().path()).cloned() {
+        // requests are what's decoded on this side
+        Some(method) => tonic::server::Grpc::new(DynamicCodec::new(method.input()))
+            .streaming(service_of(method), request)
+            .await,
+        None => Status::unimplemented(format!("`{}` is not in the schema", request.uri().path())).into_http(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_listen_address() {
+        assert_eq!(parse_listen(":9091"), Ok((String::from("0.0.0.0"), 9091)));
+        assert_eq!(parse_listen("127.0.0.1:9091"), Ok((String::from("127.0.0.1"), 9091)));
+        assert_eq!(parse_listen("[::1]:9091"), Ok((String::from("::1"), 9091)));
+        assert!(parse_listen("9091").is_err());
+        assert!(parse_listen(":http").is_err());
+    }
+}
@@ -0,0 +1,404 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use base64::Engine;
+use prost::Message;
+use serde_json::Value;
+use tonic::metadata::{KeyAndValueRef, MetadataMap};
+use crate::latency::Timing;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StatusCode {
+    Ok,
+    Cancelled,
+    Unknown,
+    InvalidArgument,
+    DeadlineExceeded,
+    NotFound,
+    AlreadyExists,
+    PermissionDenied,
+    ResourceExhausted,
+    FailedPrecondition,
+    Aborted,
+    OutOfRange,
+    Unimplemented,
+    Internal,
+    Unavailable,
+    DataLoss,
+    Unauthenticated,
+}
+
+impl StatusCode {
+    pub fn from(code: i32) -> StatusCode {
+        match code {
+            0 => StatusCode::Ok,
+            1 => StatusCode::Cancelled,
+            3 => StatusCode::InvalidArgument,
+            4 => StatusCode::DeadlineExceeded,
+            5 => StatusCode::NotFound,
+            6 => StatusCode::AlreadyExists,
+            7 => StatusCode::PermissionDenied,
+            8 => StatusCode::ResourceExhausted,
+            9 => StatusCode::FailedPrecondition,
+            10 => StatusCode::Aborted,
+            11 => StatusCode::OutOfRange,
+            12 => StatusCode::Unimplemented,
+            13 => StatusCode::Internal,
+            14 => StatusCode::Unavailable,
+            15 => StatusCode::DataLoss,
+            16 => StatusCode::Unauthenticated,
+            _ => StatusCode::Unknown,
+        }
+    }
+
+    pub fn code(&self) -> i32 {
+        *self as i32
+    }
+
+    // `UNAVAILABLE`, `unavailable` or `14`
+    pub fn from_name(s: &str) -> Result<StatusCode, String> {
+        if let Ok(code) = s.parse::<i32>() {
+            return if (0..=16).contains(&code) { Ok(StatusCode::from(code)) } else { Err(format!("Unknown status code `{}`", s)) };
+        }
+        (0..=16).map(StatusCode::from)
+            .find(|c| c.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("Unknown status code `{}`", s))
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            StatusCode::Ok                 => "OK",
+            StatusCode::Cancelled          => "CANCELLED",
+            StatusCode::Unknown            => "UNKNOWN",
+            StatusCode::InvalidArgument    => "INVALID_ARGUMENT",
+            StatusCode::DeadlineExceeded   => "DEADLINE_EXCEEDED",
+            StatusCode::NotFound           => "NOT_FOUND",
+            StatusCode::AlreadyExists      => "ALREADY_EXISTS",
+            StatusCode::PermissionDenied   => "PERMISSION_DENIED",
+            StatusCode::ResourceExhausted  => "RESOURCE_EXHAUSTED",
+            StatusCode::FailedPrecondition => "FAILED_PRECONDITION",
+            StatusCode::Aborted            => "ABORTED",
+            StatusCode::OutOfRange         => "OUT_OF_RANGE",
+            StatusCode::Unimplemented      => "UNIMPLEMENTED",
+            StatusCode::Internal           => "INTERNAL",
+            StatusCode::Unavailable        => "UNAVAILABLE",
+            StatusCode::DataLoss           => "DATA_LOSS",
+            StatusCode::Unauthenticated    => "UNAUTHENTICATED",
+        }
+    }
+}
+
+impl Display for StatusCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}({})", self.name(), self.code())
+    }
+}
+
+// google.rpc.Status and the standard error details from google/rpc/error_details.proto
+#[derive(Clone, PartialEq, Message)]
+struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    code: i32,
+    #[prost(string, tag = "2")]
+    message: String,
+    #[prost(message, repeated, tag = "3")]
+    details: Vec<prost_types::Any>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ErrorInfo {
+    #[prost(string, tag = "1")]
+    reason: String,
+    #[prost(string, tag = "2")]
+    domain: String,
+    #[prost(map = "string, string", tag = "3")]
+    metadata: HashMap<String, String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct RetryInfo {
+    #[prost(message, optional, tag = "1")]
+    retry_delay: Option<prost_types::Duration>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct DebugInfo {
+    #[prost(string, repeated, tag = "1")]
+    stack_entries: Vec<String>,
+    #[prost(string, tag = "2")]
+    detail: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct QuotaFailure {
+    #[prost(message, repeated, tag = "1")]
+    violations: Vec<QuotaViolation>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct QuotaViolation {
+    #[prost(string, tag = "1")]
+    subject: String,
+    #[prost(string, tag = "2")]
+    description: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PreconditionFailure {
+    #[prost(message, repeated, tag = "1")]
+    violations: Vec<PreconditionViolation>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PreconditionViolation {
+    #[prost(string, tag = "1")]
+    r#type: String,
+    #[prost(string, tag = "2")]
+    subject: String,
+    #[prost(string, tag = "3")]
+    description: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct BadRequest {
+    #[prost(message, repeated, tag = "1")]
+    field_violations: Vec<FieldViolation>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct FieldViolation {
+    #[prost(string, tag = "1")]
+    field: String,
+    #[prost(string, tag = "2")]
+    description: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct RequestInfo {
+    #[prost(string, tag = "1")]
+    request_id: String,
+    #[prost(string, tag = "2")]
+    serving_data: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ResourceInfo {
+    #[prost(string, tag = "1")]
+    resource_type: String,
+    #[prost(string, tag = "2")]
+    resource_name: String,
+    #[prost(string, tag = "3")]
+    owner: String,
+    #[prost(string, tag = "4")]
+    description: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Help {
+    #[prost(message, repeated, tag = "1")]
+    links: Vec<HelpLink>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct HelpLink {
+    #[prost(string, tag = "1")]
+    description: String,
+    #[prost(string, tag = "2")]
+    url: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct LocalizedMessage {
+    #[prost(string, tag = "1")]
+    locale: String,
+    #[prost(string, tag = "2")]
+    message: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ErrorDetail {
+    ErrorInfo { reason: String, domain: String, metadata: Vec<(String, String)> },
+    RetryInfo { retry_delay: std::time::Duration },
+    DebugInfo { stack_entries: Vec<String>, detail: String },
+    QuotaFailure(Vec<(String, String)>),
+    PreconditionFailure(Vec<(String, String, String)>),
+    BadRequest(Vec<(String, String)>),
+    RequestInfo { request_id: String, serving_data: String },
+    ResourceInfo { resource_type: String, resource_name: String, owner: String, description: String },
+    Help(Vec<(String, String)>),
+    LocalizedMessage { locale: String, message: String },
+    Unknown { type_url: String, value: Vec<u8> },
+}
+
+impl ErrorDetail {
+    fn from(any: &prost_types::Any) -> ErrorDetail {
+        let type_name = any.type_url.rsplit('/').next().unwrap_or_default();
+        let bytes = any.value.as_slice();
+        let decoded = match type_name {
+            "google.rpc.ErrorInfo" => ErrorInfo::decode(bytes).ok().map(|d| {
+                let mut metadata: Vec<(String, String)> = d.metadata.into_iter().collect();
+                metadata.sort();
+                ErrorDetail::ErrorInfo { reason: d.reason, domain: d.domain, metadata }
+            }),
+            "google.rpc.RetryInfo" => RetryInfo::decode(bytes).ok().map(|d| {
+                let delay = d.retry_delay.unwrap_or_default();
+                let retry_delay = std::time::Duration::new(delay.seconds.max(0) as u64, delay.nanos.max(0) as u32);
+                ErrorDetail::RetryInfo { retry_delay }
+            }),
+            "google.rpc.DebugInfo" => DebugInfo::decode(bytes).ok()
+                .map(|d| ErrorDetail::DebugInfo { stack_entries: d.stack_entries, detail: d.detail }),
+            "google.rpc.QuotaFailure" => QuotaFailure::decode(bytes).ok()
+                .map(|d| ErrorDetail::QuotaFailure(d.violations.into_iter().map(|v| (v.subject, v.description)).collect())),
+            "google.rpc.PreconditionFailure" => PreconditionFailure::decode(bytes).ok()
+                .map(|d| ErrorDetail::PreconditionFailure(d.violations.into_iter().map(|v| (v.r#type, v.subject, v.description)).collect())),
+            "google.rpc.BadRequest" => BadRequest::decode(bytes).ok()
+                .map(|d| ErrorDetail::BadRequest(d.field_violations.into_iter().map(|v| (v.field, v.description)).collect())),
+            "google.rpc.RequestInfo" => RequestInfo::decode(bytes).ok()
+                .map(|d| ErrorDetail::RequestInfo { request_id: d.request_id, serving_data: d.serving_data }),
+            "google.rpc.ResourceInfo" => ResourceInfo::decode(bytes).ok().map(|d| ErrorDetail::ResourceInfo {
+                resource_type: d.resource_type,
+                resource_name: d.resource_name,
+                owner: d.owner,
+                description: d.description,
+            }),
+            "google.rpc.Help" => Help::decode(bytes).ok()
+                .map(|d| ErrorDetail::Help(d.links.into_iter().map(|l| (l.description, l.url)).collect())),
+            "google.rpc.LocalizedMessage" => LocalizedMessage::decode(bytes).ok()
+                .map(|d| ErrorDetail::LocalizedMessage { locale: d.locale, message: d.message }),
+            _ => None,
+        };
+        decoded.unwrap_or_else(|| ErrorDetail::Unknown { type_url: any.type_url.clone(), value: any.value.clone() })
+    }
+}
+
+impl Display for ErrorDetail {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorDetail::ErrorInfo { reason, domain, metadata } => {
+                write!(f, "ErrorInfo: reason={}, domain={}", reason, domain)?;
+                metadata.iter().try_for_each(|(k, v)| write!(f, "\n    {}: {}", k, v))
+            }
+            ErrorDetail::RetryInfo { retry_delay } => write!(f, "RetryInfo: retry after {:?}", retry_delay),
+            ErrorDetail::DebugInfo { stack_entries, detail } => {
+                write!(f, "DebugInfo: {}", detail)?;
+                stack_entries.iter().try_for_each(|entry| write!(f, "\n    {}", entry))
+            }
+            ErrorDetail::QuotaFailure(violations) => {
+                write!(f, "QuotaFailure:")?;
+                violations.iter().try_for_each(|(subject, description)| write!(f, "\n    {}: {}", subject, description))
+            }
+            ErrorDetail::PreconditionFailure(violations) => {
+                write!(f, "PreconditionFailure:")?;
+                violations.iter().try_for_each(|(t, subject, description)| write!(f, "\n    [{}] {}: {}", t, subject, description))
+            }
+            ErrorDetail::BadRequest(violations) => {
+                write!(f, "BadRequest:")?;
+                violations.iter().try_for_each(|(field, description)| write!(f, "\n    {}: {}", field, description))
+            }
+            ErrorDetail::RequestInfo { request_id, serving_data } => write!(f, "RequestInfo: request_id={}, serving_data={}", request_id, serving_data),
+            ErrorDetail::ResourceInfo { resource_type, resource_name, owner, description } =>
+                write!(f, "ResourceInfo: {} `{}` owned by {}: {}", resource_type, resource_name, owner, description),
+            ErrorDetail::Help(links) => {
+                write!(f, "Help:")?;
+                links.iter().try_for_each(|(description, url)| write!(f, "\n    {}: {}", description, url))
+            }
+            ErrorDetail::LocalizedMessage { locale, message } => write!(f, "LocalizedMessage({}): {}", locale, message),
+            ErrorDetail::Unknown { type_url, value } => write!(f, "{}: {}", type_url, base64::engine::general_purpose::STANDARD.encode(value)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct GrpcStatus {
+    pub code: StatusCode,
+    pub message: String,
+    pub details: Vec<ErrorDetail>,
+}
+
+impl GrpcStatus {
+    pub fn ok() -> GrpcStatus {
+        GrpcStatus { code: StatusCode::Ok, message: String::new(), details: vec![] }
+    }
+
+    pub fn from(status: &tonic::Status) -> GrpcStatus {
+        let details = RpcStatus::decode(status.details())
+            .map(|s| s.details.iter().map(ErrorDetail::from).collect())
+            .unwrap_or_default();
+        GrpcStatus { code: StatusCode::from(status.code() as i32), message: String::from(status.message()), details }
+    }
+
+    // a status whose details came apart from `google.rpc.Status`, such as in a Connect error
+    pub fn with_details(code: StatusCode, message: String, details: &[prost_types::Any]) -> GrpcStatus {
+        GrpcStatus { code, message, details: details.iter().map(ErrorDetail::from).collect() }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.code == StatusCode::Ok
+    }
+}
+
+impl Display for GrpcStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code)?;
+        if !self.message.is_empty() {
+            write!(f, " - {}", self.message)?;
+        }
+        self.details.iter().try_for_each(|d| write!(f, "\n  {}", d))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GrpcResponse {
+    pub status: GrpcStatus,
+    pub headers: Vec<(String, String)>,
+    pub trailers: Vec<(String, String)>,
+    pub messages: Vec<Value>,
+    pub timing: Timing,
+    pub attempts: u32,
+}
+
+pub fn metadata_pairs(metadata: &MetadataMap) -> Vec<(String, String)> {
+    metadata.iter().map(|entry| match entry {
+        KeyAndValueRef::Ascii(k, v) => (k.to_string(), v.to_str().map(String::from).unwrap_or_else(|_| format!("{:?}", v))),
+        KeyAndValueRef::Binary(k, v) => (k.to_string(), v.to_bytes()
+            .map(|b| base64::engine::general_purpose::STANDARD.encode(b))
+            .unwrap_or_else(|_| format!("{:?}", v))),
+    }).collect()
+}
+
+pub fn format_metadata(pairs: &[(String, String)]) -> String {
+    if pairs.is_empty() {
+        String::from("  (empty)")
+    } else {
+        pairs.iter().map(|(k, v)| format!("  {}: {}", k, v)).collect::<Vec<String>>().join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn any<M: Message>(type_name: &str, message: M) -> prost_types::Any {
+        prost_types::Any { type_url: format!("type.googleapis.com/{}", type_name), value: message.encode_to_vec() }
+    }
+
+    #[test]
+    fn decodes_status_details() {
+        let details = RpcStatus {
+            code: 3,
+            message: String::from("invalid"),
+            details: vec![
+                any("google.rpc.BadRequest", BadRequest { field_violations: vec![FieldViolation { field: String::from("name"), description: String::from("required") }] }),
+                any("google.rpc.RetryInfo", RetryInfo { retry_delay: Some(prost_types::Duration { seconds: 1, nanos: 500_000_000 }) }),
+                any("example.Custom", RequestInfo::default()),
+            ],
+        };
+        let status = tonic::Status::with_details(tonic::Code::InvalidArgument, "invalid", details.encode_to_vec().into());
+        let parsed = GrpcStatus::from(&status);
+
+        assert_eq!(parsed.code, StatusCode::InvalidArgument);
+        assert_eq!(parsed.details[0], ErrorDetail::BadRequest(vec![(String::from("name"), String::from("required"))]));
+        assert_eq!(parsed.details[1], ErrorDetail::RetryInfo { retry_delay: std::time::Duration::from_millis(1500) });
+        assert!(matches!(parsed.details[2], ErrorDetail::Unknown { .. }));
+        assert_eq!(parsed.to_string().lines().next(), Some("INVALID_ARGUMENT(3) - invalid"));
+    }
+}
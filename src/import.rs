@@ -0,0 +1,322 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::Write;
+use log::warn;
+use serde_json::{json, Value};
+use crate::collection::{self, CollectionEntry};
+use crate::grpc_request_dsl::{SchemaFiles, ServiceRequest, Target};
+
+// grpcurl flags that take no value. The rest take one, as `-flag value` or `-flag=value`
+const GRPCURL_BOOL_FLAGS: [&str; 12] = [
+    "plaintext", "insecure", "unix", "v", "vv", "veryverbose", "emit-defaults", "allow-unknown-fields",
+    "expand-headers", "msg-template", "format-error", "use-reflection",
+];
+
+// Appends requests found in `source` to the collection at `out`
+pub fn run(source: &str, out: &str) -> Result<bool, String> {
+    let text = std::fs::read_to_string(source).map_err(|e| format!("Failed to read `{}`: {}", source, e))?;
+    let imported = match serde_json::from_str::<Value>(&text) {
+        Ok(export) if export.get("resources").is_some() => from_insomnia(&export)?,
+        Ok(export) if export.get("item").is_some() => from_postman(&export)?,
+        Ok(_) => return Err(format!("`{}` is neither a Postman nor an Insomnia export", source)),
+        Err(_) => from_grpcurl_lines(&text)?,
+    };
+    if imported.is_empty() {
+        return Err(format!("No gRPC request found in `{}`", source));
+    }
+
+    let mut names: HashSet<String> = match std::path::Path::new(out).exists() {
+        true => collection::load(out)?.into_iter().map(|e| e.name).collect(),
+        false => HashSet::new(),
+    };
+    let mut file = OpenOptions::new().create(true).append(true).open(out)
+        .map_err(|e| format!("Failed to open `{}`: {}", out, e))?;
+    for mut entry in imported {
+        entry.name = unique_name(&entry.name, &names);
+        names.insert(entry.name.clone());
+        let line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+        writeln!(file, "{}", line).map_err(|e| format!("Failed to write `{}`: {}", out, e))?;
+        println!("Imported {} ({}/{})", entry.name, entry.service, entry.function);
+    }
+    Ok(true)
+}
+
+// names are golden file names for snapshots, so they must not repeat
+fn unique_name(name: &str, taken: &HashSet<String>) -> String {
+    let name = if name.trim().is_empty() { "request" } else { name.trim() };
+    (1..).map(|i| if i == 1 { String::from(name) } else { format!("{}-{}", name, i) })
+        .find(|n| !taken.contains(n))
+        .unwrap_or_else(|| String::from(name))
+}
+
+fn entry_of(name: &str, target: &Target, method: &str, body: Value, metadata: BTreeMap<String, String>) -> Result<CollectionEntry, String> {
+    let (service, function) = ServiceRequest::parse_method(method.trim_start_matches('/'))?;
+    // a port left out is the default of the scheme
+    let port = target.port.as_ref().map_or(if target.tls { 443 } else { 80 }, |p| p.0);
+    Ok(CollectionEntry {
+        name: String::from(name),
+        host: target.host.to_string(),
+        port: if target.host.is_unix() { 0 } else { port },
+        service: service.to_string(),
+        function: function.to_string(),
+        tls: target.tls,
+        body,
+        metadata,
+        schema: SchemaFiles::default(),
+        response: None,
+        status: None,
+        elapsed_ms: None,
+    })
+}
+
+// a body written as text, which may hold a stream of messages
+fn body_of(text: &str) -> Result<Value, String> {
+    let messages = crate::util::to_json_stream(text).map_err(|e| format!("Invalid request body: {}", e))?;
+    Ok(match messages.len() {
+        0 => json!({}),
+        1 => messages[0].clone(),
+        _ => Value::Array(messages),
+    })
+}
+
+// `key: value`
+fn header_of(header: &str) -> Result<(String, String), String> {
+    header.split_once(':')
+        .map(|(k, v)| (k.trim().to_lowercase(), v.trim().to_string()))
+        .filter(|(k, _)| !k.is_empty())
+        .ok_or_else(|| format!("Invalid header `{}`. ex) `authorization: Bearer token`", header))
+}
+
+// Command lines from a wiki page or a shell history. Lines ending with `\` continue,
+// a leading `$ ` prompt is dropped and lines that aren't grpcurl are skipped
+fn from_grpcurl_lines(text: &str) -> Result<Vec<CollectionEntry>, String> {
+    let joined = text.replace("\\\r\n", " ").replace("\\\n", " ");
+    let mut entries = vec![];
+    for (i, line) in joined.lines().enumerate() {
+        let line = line.trim().trim_start_matches("$ ").trim_start_matches("> ").trim();
+        if !line.starts_with("grpcurl ") {
+            continue;
+        }
+        match from_grpcurl(line) {
+            Ok(Some(entry)) => entries.push(entry),
+            Ok(None) => {}
+            Err(e) => warn!("Skipped command {}: {}", i + 1, e),
+        }
+    }
+    Ok(entries)
+}
+
+// `None` for commands without a call, such as `list` and `describe`
+pub fn from_grpcurl(command: &str) -> Result<Option<CollectionEntry>, String> {
+    let words = split_words(command)?;
+    let mut args = words.iter().skip(1);
+    let (mut plaintext, mut unix) = (false, false);
+    let mut body = None;
+    let mut metadata = BTreeMap::new();
+    let mut schema = SchemaFiles::default();
+    let mut positional = vec![];
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') || arg == "-" {
+            positional.push(arg.clone());
+            continue;
+        }
+        let flag = arg.trim_start_matches('-');
+        let (name, inline) = match flag.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (flag, None),
+        };
+        if GRPCURL_BOOL_FLAGS.contains(&name) {
+            let on = inline.as_deref().is_none_or(|v| v == "true");
+            match name {
+                "plaintext" => plaintext = on,
+                "unix" => unix = on,
+                _ => {}
+            }
+            continue;
+        }
+        let value = match inline {
+            Some(value) => value,
+            None => args.next().cloned().ok_or_else(|| format!("`-{}` needs a value", name))?,
+        };
+        match name {
+            "d" if value == "@" => return Err(String::from("a body read from stdin(`-d @`) can't be imported")),
+            "d" => body = Some(body_of(&value)?),
+            "H" | "rpc-header" => {
+                let (key, value) = header_of(&value)?;
+                metadata.insert(key, value);
+            }
+            "protoset" => schema.protosets.push(value),
+            "proto" => schema.protos.push(value),
+            "import-path" => schema.import_paths.push(value),
+            _ => {}
+        }
+    }
+    let (address, method) = match positional.as_slice() {
+        [_, verb] | [verb] if verb == "list" || verb == "describe" => return Ok(None),
+        [_, verb, _] if verb == "list" || verb == "describe" => return Ok(None),
+        [address, method] => (address, method),
+        _ => return Err(format!("Expected an address and a method, found `{}`", positional.join(" "))),
+    };
+    let target = if unix { Target::from(&format!("unix:{}", address))? } else { Target::from(address)? };
+    let target = Target { tls: !plaintext && !target.host.is_unix(), ..target };
+    let (_, function) = ServiceRequest::parse_method(method)?;
+    let entry = entry_of(&function.to_string(), &target, method, body.unwrap_or_else(|| json!({})), metadata)?;
+    Ok(Some(CollectionEntry { schema, ..entry }))
+}
+
+// Words of a POSIX shell command line, with single quotes, double quotes and backslashes
+pub fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                let current = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => current.push(c),
+                        None => return Err(String::from("Unterminated quote")),
+                    }
+                }
+            }
+            '"' => {
+                let current = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        // only these are escaped inside double quotes
+                        Some('\\') => match chars.next() {
+                            Some(e) if "\"\\$`".contains(e) => current.push(e),
+                            Some(e) => { current.push('\\'); current.push(e); }
+                            None => return Err(String::from("Unterminated quote")),
+                        },
+                        Some(c) => current.push(c),
+                        None => return Err(String::from("Unterminated quote")),
+                    }
+                }
+            }
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    word.get_or_insert_with(String::new).push(escaped);
+                }
+            }
+            c if c.is_whitespace() => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+// Insomnia(v4 export). gRPC requests are resources of `grpc_request` type
+fn from_insomnia(export: &Value) -> Result<Vec<CollectionEntry>, String> {
+    let resources = export["resources"].as_array().ok_or("Invalid Insomnia export: `resources` is not an array")?;
+    let mut entries = vec![];
+    for resource in resources.iter().filter(|r| r["_type"] == "grpc_request") {
+        let name = resource["name"].as_str().unwrap_or("request");
+        let imported = Target::from(resource["url"].as_str().unwrap_or_default()).and_then(|target| {
+            let method = resource["protoMethodName"].as_str().ok_or("no method selected")?;
+            let body = body_of(resource["body"]["text"].as_str().unwrap_or_default())?;
+            let metadata = pairs_of(&resource["metadata"], "name");
+            entry_of(name, &target, method, body, metadata)
+        });
+        match imported {
+            Ok(entry) => entries.push(entry),
+            Err(e) => warn!("Skipped `{}`: {}", name, e),
+        }
+    }
+    Ok(entries)
+}
+
+// Postman. gRPC requests are items with a `methodPath`, in folders of any depth
+fn from_postman(export: &Value) -> Result<Vec<CollectionEntry>, String> {
+    let mut entries = vec![];
+    postman_items(export, &mut entries);
+    Ok(entries)
+}
+
+fn postman_items(folder: &Value, entries: &mut Vec<CollectionEntry>) {
+    for item in folder["item"].as_array().into_iter().flatten() {
+        if item.get("item").is_some() {
+            postman_items(item, entries);
+            continue;
+        }
+        let request = &item["request"];
+        let method = match request["methodPath"].as_str() {
+            Some(method) => method,
+            None => continue,
+        };
+        let name = item["name"].as_str().unwrap_or("request");
+        let url = request["url"].as_str().or_else(|| request["url"]["raw"].as_str()).unwrap_or_default();
+        let message = request["message"].as_str()
+            .or_else(|| request["message"]["content"].as_str())
+            .unwrap_or_default();
+        let imported = Target::from(url).and_then(|target| {
+            // TLS is a request setting rather than a part of the URL
+            let tls = target.tls || request["settings"]["tls"].as_bool().unwrap_or(false);
+            entry_of(name, &Target { tls, ..target }, method, body_of(message)?, pairs_of(&request["metadata"], "key"))
+        });
+        match imported {
+            Ok(entry) => entries.push(entry),
+            Err(e) => warn!("Skipped `{}`: {}", name, e),
+        }
+    }
+}
+
+// `[{"key": "authorization", "value": "..."}]`, without the disabled ones
+fn pairs_of(pairs: &Value, key: &str) -> BTreeMap<String, String> {
+    pairs.as_array().into_iter().flatten()
+        .filter(|p| !p["disabled"].as_bool().unwrap_or(false))
+        .filter_map(|p| Some((p[key].as_str()?.to_lowercase(), p["value"].as_str()?.to_string())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_shell_words() {
+        assert_eq!(split_words(r#"grpcurl -d '{"name": "O'\''Brien"}' -H "x-id: \"1\"" a\ b"#).unwrap(),
+                   vec!["grpcurl", "-d", r#"{"name": "O'Brien"}"#, "-H", r#"x-id: "1""#, "a b"]);
+        assert!(split_words("grpcurl -d '{}").is_err());
+    }
+
+    #[test]
+    fn import_grpcurl_command() {
+        let entry = from_grpcurl(r#"grpcurl -plaintext -H 'Authorization: Bearer t' -d '{"name": "John"}' -import-path ./protos -proto hello.proto localhost:9090 helloworld.Greeter/SayHello"#)
+            .unwrap().unwrap();
+        assert_eq!((entry.host.as_str(), entry.port, entry.tls), ("localhost", 9090, false));
+        assert_eq!((entry.service.as_str(), entry.function.as_str()), ("helloworld.Greeter", "SayHello"));
+        assert_eq!(entry.body, json!({"name": "John"}));
+        assert_eq!(entry.metadata.get("authorization").map(String::as_str), Some("Bearer t"));
+        assert_eq!((entry.schema.protos.as_slice(), entry.schema.import_paths.as_slice()), (&[String::from("hello.proto")][..], &[String::from("./protos")][..]));
+
+        let tls = from_grpcurl("grpcurl -d={} api.example.com:443 helloworld.Greeter.SayHello").unwrap().unwrap();
+        assert!(tls.tls && tls.function == "SayHello");
+        assert!(from_grpcurl("grpcurl -plaintext localhost:9090 list").unwrap().is_none());
+        assert!(from_grpcurl("grpcurl -plaintext -d @ localhost:9090 a.B/C").is_err());
+    }
+
+    #[test]
+    fn import_insomnia_export() {
+        let export = json!({"resources": [
+            {"_type": "workspace", "name": "ws"},
+            {"_type": "grpc_request", "name": "Say hello", "url": "grpcs://api.example.com", "protoMethodName": "/helloworld.Greeter/SayHello",
+             "body": {"text": "{\"name\": \"John\"}"}, "metadata": [{"name": "x-id", "value": "1"}, {"name": "x-off", "value": "2", "disabled": true}]}
+        ]});
+        let entries = from_insomnia(&export).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].port, entries[0].tls, entries[0].function.as_str()), (443, true, "SayHello"));
+        assert_eq!(entries[0].metadata, BTreeMap::from([(String::from("x-id"), String::from("1"))]));
+    }
+
+    #[test]
+    fn names_do_not_repeat() {
+        let taken = HashSet::from([String::from("SayHello"), String::from("SayHello-2")]);
+        assert_eq!(unique_name("SayHello", &taken), "SayHello-3");
+        assert_eq!(unique_name("Other", &taken), "Other");
+    }
+}
@@ -0,0 +1,183 @@
+use std::fmt::{Display, Formatter};
+use serde_json::Value;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+    Any,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct JsonPath(pub Vec<PathSegment>);
+
+impl JsonPath {
+    pub fn root() -> JsonPath { JsonPath(vec![]) }
+
+    // Accepts `a.b`, `$.a.b`, `items[0].id`, `items[*].id` and `items.*.id`
+    pub fn from(s: &str) -> Result<JsonPath, String> {
+        let trimmed = s.trim();
+        let trimmed = trimmed.strip_prefix('$').unwrap_or(trimmed);
+        let mut segments = vec![];
+        for part in trimmed.split('.').filter(|p| !p.is_empty()) {
+            let (key, mut rest) = match part.find('[') {
+                Some(i) => (&part[..i], &part[i..]),
+                None => (part, ""),
+            };
+            match key {
+                "" => (),
+                "*" => segments.push(PathSegment::Any),
+                k => segments.push(PathSegment::Key(String::from(k))),
+            }
+            while !rest.is_empty() {
+                let close = rest.find(']').ok_or_else(|| format!("Unclosed `[` in path `{}`", s))?;
+                let inner = &rest[1..close];
+                let segment = if inner == "*" {
+                    PathSegment::Any
+                } else {
+                    PathSegment::Index(inner.parse::<usize>().map_err(|_| format!("Invalid index `{}` in path `{}`", inner, s))?)
+                };
+                segments.push(segment);
+                rest = &rest[close + 1..];
+                if !rest.is_empty() && !rest.starts_with('[') {
+                    return Err(format!("Unexpected `{}` in path `{}`", rest, s));
+                }
+            }
+        }
+        Ok(JsonPath(segments))
+    }
+
+    pub fn child(&self, segment: PathSegment) -> JsonPath {
+        let mut segments = self.0.clone();
+        segments.push(segment);
+        JsonPath(segments)
+    }
+
+    pub fn matches(&self, concrete: &JsonPath) -> bool {
+        self.0.len() == concrete.0.len() && self.0.iter().zip(concrete.0.iter()).all(|(pattern, segment)| {
+            match (pattern, segment) {
+                (PathSegment::Any, _) => true,
+                (p, s) => p == s,
+            }
+        })
+    }
+}
+
+impl Display for JsonPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "$")?;
+        for segment in &self.0 {
+            match segment {
+                PathSegment::Key(k) => write!(f, ".{}", k)?,
+                PathSegment::Index(i) => write!(f, "[{}]", i)?,
+                PathSegment::Any => write!(f, "[*]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Difference {
+    Changed { path: JsonPath, expected: Value, actual: Value },
+    Missing { path: JsonPath, expected: Value },
+    Added { path: JsonPath, actual: Value },
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difference::Changed { path, expected, actual } => write!(f, "~ {}: {} -> {}", path, expected, actual),
+            Difference::Missing { path, expected } => write!(f, "- {}: {}", path, expected),
+            Difference::Added { path, actual } => write!(f, "+ {}: {}", path, actual),
+        }
+    }
+}
+
+pub fn diff(expected: &Value, actual: &Value, ignore: &[JsonPath]) -> Vec<Difference> {
+    let mut differences = vec![];
+    diff_at(&JsonPath::root(), expected, actual, ignore, &mut differences);
+    differences
+}
+
+fn diff_at(path: &JsonPath, expected: &Value, actual: &Value, ignore: &[JsonPath], acc: &mut Vec<Difference>) {
+    if ignore.iter().any(|p| p.matches(path)) {
+        return;
+    }
+    match (expected, actual) {
+        (Value::Object(e), Value::Object(a)) => {
+            for (key, e_value) in e {
+                let child = path.child(PathSegment::Key(key.clone()));
+                match a.get(key) {
+                    Some(a_value) => diff_at(&child, e_value, a_value, ignore, acc),
+                    None => if !ignore.iter().any(|p| p.matches(&child)) {
+                        acc.push(Difference::Missing { path: child, expected: e_value.clone() })
+                    },
+                }
+            }
+            for (key, a_value) in a.iter().filter(|(k, _)| !e.contains_key(*k)) {
+                let child = path.child(PathSegment::Key(key.clone()));
+                if !ignore.iter().any(|p| p.matches(&child)) {
+                    acc.push(Difference::Added { path: child, actual: a_value.clone() })
+                }
+            }
+        }
+        (Value::Array(e), Value::Array(a)) => {
+            for i in 0..e.len().max(a.len()) {
+                let child = path.child(PathSegment::Index(i));
+                if ignore.iter().any(|p| p.matches(&child)) { continue; }
+                match (e.get(i), a.get(i)) {
+                    (Some(e_value), Some(a_value)) => diff_at(&child, e_value, a_value, ignore, acc),
+                    (Some(e_value), None) => acc.push(Difference::Missing { path: child, expected: e_value.clone() }),
+                    (None, Some(a_value)) => acc.push(Difference::Added { path: child, actual: a_value.clone() }),
+                    (None, None) => (),
+                }
+            }
+        }
+        (e, a) => if e != a {
+            acc.push(Difference::Changed { path: path.clone(), expected: e.clone(), actual: a.clone() })
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_path() {
+        let parsed = JsonPath::from("$.items[*].id").unwrap();
+        let expected = JsonPath(vec![PathSegment::Key(String::from("items")), PathSegment::Any, PathSegment::Key(String::from("id"))]);
+        assert_eq!(parsed, expected);
+        assert_eq!(JsonPath::from("items.*.id").unwrap(), expected);
+        assert!(JsonPath::from("items[x]").is_err());
+    }
+
+    #[test]
+    fn identical_values_have_no_difference() {
+        let value = json!({"name": "john", "tags": [1, 2]});
+        assert!(diff(&value, &value, &[]).is_empty());
+    }
+
+    #[test]
+    fn reports_changed_missing_and_added() {
+        let expected = json!({"name": "john", "age": 3, "tags": [1]});
+        let actual = json!({"name": "johnny", "tags": [1, 2], "nick": "j"});
+        let rendered: Vec<String> = diff(&expected, &actual, &[]).iter().map(|d| d.to_string()).collect();
+        assert_eq!(rendered, vec![
+            "- $.age: 3",
+            "~ $.name: \"john\" -> \"johnny\"",
+            "+ $.tags[1]: 2",
+            "+ $.nick: \"j\"",
+        ]);
+    }
+
+    #[test]
+    fn ignores_volatile_paths() {
+        let expected = json!([{"items": [{"id": "a", "v": 1}], "createdAt": "yesterday"}]);
+        let actual = json!([{"items": [{"id": "b", "v": 1}], "createdAt": "today"}]);
+        let ignore = vec![JsonPath::from("[*].items[*].id").unwrap(), JsonPath::from("[0].createdAt").unwrap()];
+        assert!(diff(&expected, &actual, &ignore).is_empty());
+    }
+}
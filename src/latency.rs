@@ -0,0 +1,114 @@
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default)]
+pub struct Timing {
+    pub connect: Duration,
+    pub first_message: Option<Duration>,
+    pub inter_arrival: Vec<Duration>,
+    pub total: Duration,
+}
+
+impl Display for Timing {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "total {} (connect {}", format_duration(self.total), format_duration(self.connect))?;
+        if let Some(first) = self.first_message {
+            write!(f, ", first message {}", format_duration(first))?;
+        }
+        write!(f, ")")?;
+        if !self.inter_arrival.is_empty() {
+            let gaps: Vec<String> = self.inter_arrival.iter().map(|d| format_duration(*d)).collect();
+            write!(f, "\n  inter-arrival: {}", gaps.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+pub fn format_duration(d: Duration) -> String {
+    if d >= Duration::from_secs(1) {
+        format!("{:.2}s", d.as_secs_f64())
+    } else {
+        format!("{:.2}ms", d.as_secs_f64() * 1000.0)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LatencyStats {
+    samples: Vec<Duration>,
+}
+
+impl LatencyStats {
+    pub fn new() -> LatencyStats { LatencyStats { samples: vec![] } }
+
+    pub fn push(&mut self, d: Duration) {
+        self.samples.push(d);
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    pub fn count(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn min(&self) -> Duration {
+        self.samples.iter().min().copied().unwrap_or_default()
+    }
+
+    pub fn max(&self) -> Duration {
+        self.samples.iter().max().copied().unwrap_or_default()
+    }
+
+    pub fn average(&self) -> Duration {
+        if self.samples.is_empty() { Duration::ZERO } else {
+            self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+        }
+    }
+
+    // nearest-rank percentile
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::ZERO;
+        }
+        let mut sorted = self.samples.clone();
+        sorted.sort();
+        let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+        sorted[rank.clamp(1, sorted.len()) - 1]
+    }
+}
+
+impl Display for LatencyStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "min {} / avg {} / p50 {} / p95 {} / max {}",
+               format_duration(self.min()),
+               format_duration(self.average()),
+               format_duration(self.percentile(50.0)),
+               format_duration(self.percentile(95.0)),
+               format_duration(self.max()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_of_samples() {
+        let mut stats = LatencyStats::new();
+        (1..=20).for_each(|i| stats.push(Duration::from_millis(i)));
+        assert_eq!(stats.min(), Duration::from_millis(1));
+        assert_eq!(stats.max(), Duration::from_millis(20));
+        assert_eq!(stats.average(), Duration::from_micros(10_500));
+        assert_eq!(stats.percentile(50.0), Duration::from_millis(10));
+        assert_eq!(stats.percentile(95.0), Duration::from_millis(19));
+        assert_eq!(stats.percentile(100.0), Duration::from_millis(20));
+    }
+
+    #[test]
+    fn empty_summary() {
+        let stats = LatencyStats::new();
+        assert_eq!(stats.percentile(95.0), Duration::ZERO);
+        assert_eq!(stats.average(), Duration::ZERO);
+    }
+}
@@ -1,35 +1,240 @@
-use config::Config;
-use log::{Record, Level, Metadata};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::{Record, Metadata};
 use log::LevelFilter;
+use serde::{de, Deserialize, Deserializer};
+use crate::util::{deserialize_size, state_dir};
 
-struct SimpleLogger;
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Destination {
+    Stdout,
+    Stderr,
+    // `zrpc-cli.log` under the state directory, rotated by size
+    File,
+}
+
+impl Destination {
+    pub fn from(s: &str) -> Result<Destination, String> {
+        match s.to_lowercase().as_str() {
+            "stdout" => Ok(Destination::Stdout),
+            "stderr" => Ok(Destination::Stderr),
+            "file" => Ok(Destination::File),
+            _ => Err(format!("Invalid log destination `{}`. stdout, stderr or file", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for Destination {
+    type Error = String;
+    fn try_from(s: String) -> Result<Destination, String> {
+        Destination::from(&s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum LogFormat {
+    // `LEVEL - msg`, with the time and the module in a file
+    Text,
+    // a JSON object per line
+    Json,
+}
+
+impl LogFormat {
+    pub fn from(s: &str) -> Result<LogFormat, String> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Invalid log format `{}`. text or json", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for LogFormat {
+    type Error = String;
+    fn try_from(s: String) -> Result<LogFormat, String> {
+        LogFormat::from(&s)
+    }
+}
+
+// `[log]` of settings
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LogOptions {
+    pub destination: Destination,
+    pub format: LogFormat,
+    // levels of modules and everything under them. ex) (`rustls`, warn), (`zrpc_cli::proxy`, debug)
+    #[serde(default, deserialize_with = "deserialize_modules")]
+    pub modules: Vec<(String, LevelFilter)>,
+    // a file larger than this is rotated, keeping `max_files` of the old ones
+    #[serde(deserialize_with = "deserialize_size")]
+    pub max_size: u64,
+    pub max_files: usize,
+}
+
+struct Logger {
+    level: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+    format: LogFormat,
+    output: Output,
+}
+
+enum Output {
+    Stdout,
+    Stderr,
+    File(Mutex<RotatingFile>),
+}
 
-impl log::Log for SimpleLogger {
+impl Logger {
+    // the most specific module wins
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules.iter()
+            .filter(|(module, _)| target == module || target.starts_with(&format!("{}::", module)))
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.level, |(_, level)| *level)
+    }
+
+    fn line_of(&self, record: &Record) -> String {
+        match (self.format, &self.output) {
+            (LogFormat::Json, _) => serde_json::json!({
+                "timestamp": timestamp_of(SystemTime::now()),
+                "level": record.level().to_string(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            }).to_string(),
+            (LogFormat::Text, Output::File(_)) => format!("{} {} {} - {}", timestamp_of(SystemTime::now()), record.level(), record.target(), record.args()),
+            (LogFormat::Text, _) => format!("{} - {}", record.level(), record.args()),
+        }
+    }
+}
+
+impl log::Log for Logger {
     fn enabled(&self, metadata: &Metadata) -> bool {
-        metadata.level() <= Level::Trace
+        metadata.level() <= self.level_for(metadata.target())
     }
 
     fn log(&self, record: &Record) {
         if self.enabled(record.metadata()) {
-            println!("{} - {}", record.level(), record.args());
+            let line = self.line_of(record);
+            match &self.output {
+                Output::Stdout => println!("{}", line),
+                Output::Stderr => eprintln!("{}", line),
+                Output::File(file) => {
+                    if let Ok(mut file) = file.lock() {
+                        file.write_line(&line);
+                    }
+                }
+            }
         }
     }
 
-    fn flush(&self) {}
+    fn flush(&self) {
+        if let Output::File(file) = &self.output {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.file.flush();
+            }
+        }
+    }
 }
 
-static LOGGER: SimpleLogger = SimpleLogger;
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+pub fn init(level: LevelFilter, options: &LogOptions) -> Result<(), String> {
+    let output = match options.destination {
+        Destination::Stdout => Output::Stdout,
+        Destination::Stderr => Output::Stderr,
+        Destination::File => Output::File(Mutex::new(RotatingFile::open(log_file()?, options.max_size, options.max_files)?)),
+    };
+    let max_level = options.modules.iter().map(|(_, l)| *l).fold(level, Ord::max);
+    let logger = LOGGER.get_or_init(|| Logger { level, modules: options.modules.clone(), format: options.format, output });
+    log::set_logger(logger)
+        .map(|()| log::set_max_level(max_level)).map_err(|e| format!("Logger setting error: {:?}", e))
+}
 
-pub fn init(config: Config) -> Result<(), String> {
-    let level_string = config.get_string("log_level").map_err(|e| format!("config error : {:?}", e))?;
-    let level = from_str(level_string)?;
+pub fn log_file() -> Result<PathBuf, String> {
+    state_dir().map(|d| d.join("logs").join("zrpc-cli.log"))
+}
 
-    log::set_logger(&LOGGER)
-        .map(|()| log::set_max_level(level)).map_err(|e| format!("Logger setting error: {:?}", e))
+// `-v` for debug, `-vv` and more for trace
+pub fn level_of_verbosity(verbose: u8) -> Option<LevelFilter> {
+    match verbose {
+        0 => None,
+        1 => Some(LevelFilter::Debug),
+        _ => Some(LevelFilter::Trace),
+    }
 }
 
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+}
 
-fn from_str(level: String) -> Result<LevelFilter, String> {
+impl RotatingFile {
+    fn open(path: PathBuf, max_size: u64, max_files: usize) -> Result<RotatingFile, String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)
+            .map_err(|e| format!("Failed to open log file {}: {}", path.display(), e))?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or_default();
+        Ok(RotatingFile { path, file, size, max_size, max_files })
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.size > 0 && self.size + line.len() as u64 + 1 > self.max_size {
+            self.rotate();
+        }
+        if writeln!(self.file, "{}", line).is_ok() {
+            self.size += line.len() as u64 + 1;
+        }
+    }
+
+    // zrpc-cli.log -> zrpc-cli.log.1 -> zrpc-cli.log.2 ..., dropping the oldest
+    fn rotate(&mut self) {
+        let rotated = |n: usize| PathBuf::from(format!("{}.{}", self.path.display(), n));
+        let _ = std::fs::remove_file(rotated(self.max_files));
+        for n in (1..self.max_files).rev() {
+            let _ = std::fs::rename(rotated(n), rotated(n + 1));
+        }
+        let _ = match self.max_files {
+            0 => std::fs::remove_file(&self.path),
+            _ => std::fs::rename(&self.path, rotated(1)),
+        };
+        if let Ok(file) = OpenOptions::new().create(true).write(true).truncate(true).open(&self.path) {
+            self.file = file;
+            self.size = 0;
+        }
+    }
+}
+
+// RFC 3339 in UTC with milliseconds. ex) 2024-03-01T09:30:00.250Z
+fn timestamp_of(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (days, seconds_of_day) = ((seconds / 86400) as i64, seconds % 86400);
+    // days to a civil date, from Howard Hinnant's `civil_from_days`
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z", year, month, day,
+            seconds_of_day / 3600, seconds_of_day % 3600 / 60, seconds_of_day % 60, since_epoch.subsec_millis())
+}
+
+pub fn level_of(level: &str) -> Result<LevelFilter, String> {
     match level.to_lowercase().as_str() {
         "off" => Ok(LevelFilter::Off),
         "error" => Ok(LevelFilter::Error),
@@ -39,4 +244,40 @@ fn from_str(level: String) -> Result<LevelFilter, String> {
         "trace" => Ok(LevelFilter::Trace),
         _ => Err(String::from(format!("Invalid string for log level: {:?}", level)))
     }
-}
\ No newline at end of file
+}
+
+// `module = "level"`, in the order of the modules
+fn deserialize_modules<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(String, LevelFilter)>, D::Error> {
+    BTreeMap::<String, String>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(module, level)| level_of(&level).map(|level| (module, level)))
+        .collect::<Result<Vec<(String, LevelFilter)>, String>>()
+        .map_err(de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp_of(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(timestamp_of(UNIX_EPOCH + Duration::from_millis(1709285400250)), "2024-03-01T09:30:00.250Z");
+        assert_eq!(timestamp_of(UNIX_EPOCH + Duration::from_secs(951782400)), "2000-02-29T00:00:00.000Z");
+    }
+
+    #[test]
+    fn most_specific_module_wins() {
+        let logger = Logger {
+            level: LevelFilter::Info,
+            modules: vec![(String::from("zrpc_cli"), LevelFilter::Warn), (String::from("zrpc_cli::proxy"), LevelFilter::Trace)],
+            format: LogFormat::Text,
+            output: Output::Stderr,
+        };
+        assert_eq!(logger.level_for("zrpc_cli::proxy"), LevelFilter::Trace);
+        assert_eq!(logger.level_for("zrpc_cli::proxy_rules"), LevelFilter::Warn);
+        assert_eq!(logger.level_for("zrpc_cli"), LevelFilter::Warn);
+        assert_eq!(logger.level_for("rustls::client"), LevelFilter::Info);
+    }
+}
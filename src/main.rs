@@ -1,5 +1,6 @@
 use std::ops::Index;
-use log::error;
+use clap::Parser;
+use log::{debug, error, warn};
 use colored::*;
 
 mod grpc_request_dsl;
@@ -12,36 +13,119 @@ mod smart_parser;
 mod logger;
 mod config_loader;
 mod text_coloring;
+mod cli;
+mod collection;
+mod json_diff;
+mod snapshot;
+mod descriptors;
+mod response_format;
+mod session;
+mod response_filter;
+mod call;
+mod dynamic_codec;
+mod grpc_status;
+mod latency;
+mod bench;
+mod body_template;
+mod retry;
+mod recent_targets;
+mod descriptor_cache;
+mod schema_diff;
+mod proto_export;
+mod body_skeleton;
+mod docs;
+mod grpc_server;
+mod mock_server;
+mod record_proxy;
+mod import;
+mod snippet;
+mod web_client;
+mod proxy;
+mod settings;
 
 use grpc_request_dsl::*;
 use user_input::*;
 use util::*;
 use smart_parser::*;
+use crate::cli::{Cli, SubCommand};
 use crate::commands::Commands;
+use crate::grpc_status::{format_metadata, GrpcResponse, StatusCode};
+use crate::response_filter::Filter;
+use crate::response_format::OutputFormat;
+use crate::retry::RetryPolicy;
+use crate::descriptor_cache::Refreshed;
+use crate::session::Session;
+use crate::snippet::SnippetFormat;
+use crate::settings::{Profile, Settings};
 use crate::text_coloring::{to_success, to_error, to_plain_msg, to_warn, to_unknown, to_plain};
 
 fn print_divider() -> () {
     println!("---------------------------------------------------\n")
 }
 
+fn print_status(response: &GrpcResponse) {
+    let status = response.status.to_string();
+    match response.status.code {
+        StatusCode::Ok => println!("Status: {}", to_success(&status)),
+        StatusCode::DeadlineExceeded => {
+            println!("Status: {}", to_warn(&status));
+            println!("{}", to_warn("No response within the deadline. Set a longer one with option 9"));
+        }
+        _ => println!("Status: {}", to_error(&status)),
+    }
+    if response.attempts > 1 {
+        println!("Attempts: {}", response.attempts);
+    }
+    println!("Response headers:\n{}", format_metadata(&response.headers));
+    println!("Response trailers:\n{}", format_metadata(&response.trailers));
+}
+
 fn handle_command(
     command: &mut Commands,
     service_request: &mut ServiceRequest,
     user_input: &mut UserInput,
+    session: &mut Session,
+    settings: &Settings,
 ) -> Result<(), String> {
     print_divider();
     match &command {
         Commands::UpdateHost => {
+            let recent = &session.recent_targets.entries;
+            if !recent.is_empty() {
+                println!("Recent targets. Type index to resume");
+                recent.iter().enumerate().for_each(|(i, r)| println!("[{}] {}", i, r));
+            }
+            // numbered after the recent targets
+            if !settings.profiles.is_empty() {
+                println!("Profiles. Type index to use");
+                settings.profiles.iter().enumerate().for_each(|(i, (name, p))| println!("[{}] {} {}", recent.len() + i, name, p.target));
+            }
             command.print_command_message();
-            emptiable_input(user_input, "localhost");
-            let host = Host::from(user_input.get_last_input())?;
-            service_request.update_host(host);
-            Ok(command.set_next_step())
+            println!("{}", to_plain_msg(&format!("Default: {}", settings.defaults.host)));
+            emptiable_input(user_input, &settings.defaults.host);
+            if let Ok(index) = parse_usize(user_input.get_last_input(), &(recent.len() + settings.profiles.len())) {
+                if index < recent.len() {
+                    service_request.metadata.clear();
+                    recent[index].apply_to(service_request)?;
+                    command.set(Commands::TakeBodyInput);
+                } else if let Some(profile) = settings.profiles.values().nth(index - recent.len()) {
+                    command.set(apply_profile(profile, service_request));
+                }
+                return Ok(());
+            }
+            service_request.metadata.clear();
+            let target = Target::from_input(&user_input.get_last_input(), settings.defaults.tls)?;
+            // unix domain sockets have no port to ask for
+            let skip_port = target.port.is_some() || target.host.is_unix();
+            service_request.update_target(target);
+            if skip_port { command.set(Commands::SendServiceListRequest) } else { command.set_next_step() }
+            Ok(())
         }
 
         Commands::TakePortInput => {
             command.print_command_message();
-            emptiable_input(user_input, "9090");
+            println!("{}", to_plain_msg(&format!("Default: {}", settings.defaults.port)));
+            emptiable_input(user_input, &settings.defaults.port.to_string());
             let port = Port::from(user_input.get_last_input())?;
             service_request.update_port(port);
             Ok(command.set_next_step())
@@ -49,7 +133,7 @@ fn handle_command(
 
         Commands::SendServiceListRequest => {
             command.print_command_message();
-            let services = grpc_client::request_service_list(&service_request.host, &service_request.port);
+            let services = grpc_client::request_service_list(&service_request.target(), &service_request.timeouts, &service_request.network);
             services.iter().enumerate().for_each(|(i, s)| println!("[{}] {}", i, s.0));
             let _ = non_empty_input(user_input)?;
             let user_selection_index = parse_usize(user_input.get_last_input(), &services.len())?;
@@ -74,17 +158,19 @@ fn handle_command(
             let joined = multi_line_input().map(|lines| lines.join("\n"))?;
             match to_json(&joined) {
                 Ok(j) => {
+                    validate_body(service_request, &j.to_string())?;
                     service_request.update_body(j.to_string());
                     Ok(command.set_next_step())
                 }
                 Err(_) => {
-                    let j = SmartParser::new(joined.as_str()).parse()?;
+                    let j = SmartParser::new(joined.as_str()).with_max_attempt(settings.auto_correction.max_attempt).parse()?;
                     let json_string = (&j).to_string();
                     println!("Invalid JSON format. Did you mean this instead?\n\n => {}", json_string.blue());
                     println!("{}{}", "\n\t1: Yes", "\n\t2: No".color("red"));
                     non_empty_input(user_input)?;
                     let user_selection_index = parse_usize(user_input.get_last_input(), &(2 as usize))?;
                     if user_selection_index == 1 {
+                        validate_body(service_request, &j.to_string())?;
                         service_request.update_body(j.to_string());
                         command.set_next_step()
                     }
@@ -95,9 +181,23 @@ fn handle_command(
 
         Commands::SendRequest => {
             command.print_command_message();
-            let response = grpc_client::request(&service_request)?;
-            println!("Server response:\n{}", response);
-            Ok(command.set_next_step())
+            let response = grpc_client::request(service_request)?;
+            command.set_next_step();
+            session.recent_targets.record(service_request);
+            if let Err(e) = session.recent_targets.save() {
+                warn!("{}", e);
+            }
+            print_status(&response);
+            println!("Latency: {}", response.timing);
+            session.record_latency(service_request, response.timing.total);
+            if session.latency_stats.count() > 1 {
+                println!("Session({} requests): {}", session.latency_stats.count(), session.latency_stats);
+            }
+            session.last_response = response.messages;
+            if session.last_response.is_empty() { Ok(()) } else {
+                println!("Server response:");
+                session.print_response(service_request)
+            }
         }
 
         Commands::EndOfRequestSelection => {
@@ -110,22 +210,213 @@ fn handle_command(
                 "4" => command.set(Commands::SendFunctionListRequest),
                 "5" => command.set(Commands::TakeBodyInput),
                 "6" | "" => command.set(Commands::SendRequest),
-                "7" | "exit" => command.set(Commands::Exit),
+                "7" => command.set(Commands::SelectOutputFormat),
+                "8" => command.set(Commands::SetResponseFilter),
+                "9" => command.set(Commands::SetTimeouts),
+                "10" => command.set(Commands::RefreshSchema),
+                "11" => command.set(Commands::CopyAs),
+                "12" | "exit" => command.set(Commands::Exit),
                 _ => println!("Invalid input. Type again"),
             }
             Ok(())
         }
+        Commands::SelectOutputFormat => {
+            command.print_command_message();
+            OutputFormat::ALL.iter().enumerate().for_each(|(i, f)| {
+                let current = if *f == session.output_format { " (current)" } else { "" };
+                println!("[{}] {} - {}{}", i, f, f.description(), current)
+            });
+            non_empty_input(user_input)?;
+            let user_selection_index = parse_usize(user_input.get_last_input(), &OutputFormat::ALL.len())?;
+            session.set_output_format(OutputFormat::ALL[user_selection_index])?;
+            command.set_next_step();
+            if session.last_response.is_empty() { Ok(()) } else {
+                print_divider();
+                println!("Server response:");
+                session.print_response(service_request)
+            }
+        }
+        Commands::SetResponseFilter => {
+            command.print_command_message();
+            emptiable_input(user_input, "");
+            let expression = user_input.get_last_input();
+            session.set_filter(if expression.is_empty() { None } else { Some(Filter::from(&expression)?) })?;
+            command.set_next_step();
+            if session.last_response.is_empty() { Ok(()) } else {
+                print_divider();
+                println!("Server response:");
+                session.print_response(service_request)
+            }
+        }
+        Commands::SetTimeouts => {
+            command.print_command_message();
+            let current = service_request.timeouts;
+            println!("Current: {}", current);
+            println!("deadline:");
+            emptiable_input(user_input, "");
+            let deadline = match user_input.get_last_input().as_str() {
+                "" => current.deadline,
+                s => Timeouts::parse_deadline(s)?,
+            };
+            println!("connect timeout:");
+            emptiable_input(user_input, "");
+            let connect = match user_input.get_last_input().as_str() {
+                "" => current.connect,
+                s => parse_duration(s)?,
+            };
+            service_request.update_timeouts(Timeouts { connect, deadline });
+            println!("Updated: {}", service_request.timeouts);
+            Ok(command.set_next_step())
+        }
+        Commands::RefreshSchema => {
+            command.print_command_message();
+            command.set_next_step();
+            match descriptor_cache::refresh(&service_request.target(), &service_request.timeouts, &service_request.network)? {
+                Refreshed::Unchanged => println!("{}", to_success("Schema unchanged")),
+                Refreshed::Changed { previous: None, current } => println!("{}", to_success(&format!("Schema fetched({})", &current[..12]))),
+                Refreshed::Changed { previous: Some(previous), current } =>
+                    println!("{}", to_warn(&format!("Schema changed({} -> {})", &previous[..12], &current[..12]))),
+            }
+            Ok(())
+        }
+        Commands::CopyAs => {
+            command.print_command_message();
+            SnippetFormat::ALL.iter().enumerate().for_each(|(i, f)| println!("[{}] {} - {}", i, f, f.description()));
+            non_empty_input(user_input)?;
+            let user_selection_index = parse_usize(user_input.get_last_input(), &SnippetFormat::ALL.len())?;
+            command.set_next_step();
+            let snippet = snippet::render(SnippetFormat::ALL[user_selection_index], service_request)?;
+            print_divider();
+            println!("{}", snippet);
+            print_divider();
+            Ok(())
+        }
         Commands::Exit => Ok(())
     }
 }
 
+// checked against the schema, so a wrong field is caught before sending. Skipped when the schema is unavailable
+fn validate_body(service_request: &ServiceRequest, body: &str) -> Result<(), String> {
+    match descriptors::method_of(service_request) {
+        Ok(method) => grpc_client::encode_body(&method, body).map(|_| ()),
+        Err(_) => Ok(()),
+    }
+}
+
+fn run_sub_command(sub_command: SubCommand, settings: &Settings) -> Result<bool, String> {
+    let (timeouts, network) = (settings.timeouts, settings.network());
+    match sub_command {
+        SubCommand::Call { target, method, data, filter } => call::run(&target, &method, data, filter, settings),
+        SubCommand::Bench { target, method, data, total, duration, rps, concurrency, connections } => {
+            // a load test measures every single attempt, so retries are not applied
+            let mut service_request = ServiceRequest::parse(&target, &method, network)?;
+            service_request.update_timeouts(timeouts);
+            if let Some(body) = data {
+                service_request.update_body(body);
+            }
+            let duration = duration.map(|d| parse_duration(&d)).transpose()?;
+            bench::run(&service_request, bench::BenchOptions { total, duration, rps, concurrency, connections })
+        }
+        SubCommand::SchemaDiff { old, new } => schema_diff::run(&old, &new, timeouts, &network),
+        SubCommand::Export { source, format, out } => proto_export::run(&source, proto_export::ExportFormat::from(&format)?, out, timeouts, &network),
+        SubCommand::Docs { source, format, out } => docs::run(&source, docs::DocsFormat::from(&format)?, out, timeouts, &network),
+        SubCommand::Mock { protoset, host, port, rules } => mock_server::run(&protoset, &host, port, rules, timeouts, &network),
+        SubCommand::Record { listen, upstream, out, rules } => record_proxy::run(&listen, &upstream, &out, rules, timeouts, &network),
+        SubCommand::Import { source, out } => import::run(&source, &out),
+        SubCommand::Snapshot { collection, dir, ignore, update } => snapshot::run(&collection, &dir, &ignore, update, timeouts, &settings.retry, &network),
+    }
+}
+
+// skips the prompts the profile answers
+fn apply_profile(profile: &Profile, service_request: &mut ServiceRequest) -> Commands {
+    service_request.update_target(profile.target.clone());
+    service_request.metadata = profile.metadata.clone();
+    match &profile.default_service {
+        Some(service) => {
+            service_request.update_service(service.clone());
+            Commands::SendFunctionListRequest
+        }
+        None => Commands::SendServiceListRequest,
+    }
+}
+
+// command line flags take precedence over config
+fn apply_flags(cli: &Cli, settings: &mut Settings) -> Result<(), String> {
+    if let Some(format) = &cli.output {
+        settings.output.format = OutputFormat::from(format)?;
+    }
+    if let Some(connect) = &cli.connect_timeout {
+        settings.timeouts.connect = parse_duration(connect)?;
+    }
+    if let Some(deadline) = &cli.deadline {
+        settings.timeouts.deadline = Timeouts::parse_deadline(deadline)?;
+    }
+    if let Some(max_attempts) = cli.max_attempts {
+        settings.retry.max_attempts = max_attempts;
+    }
+    if let Some(codes) = &cli.retry_on {
+        settings.retry.retryable = RetryPolicy::parse_codes(codes)?;
+    }
+    if let Some(level) = logger::level_of_verbosity(cli.verbose) {
+        settings.log_level = level;
+    }
+    Ok(())
+}
+
 fn main() {
-    let config = config_loader::config();
-    let _ = logger::init(config);
+    let cli = Cli::parse();
+    let loaded = config_loader::config(cli.config.as_deref())
+        .and_then(|loaded| Settings::from(&loaded.config).map(|settings| (settings, loaded.files)))
+        .and_then(|(mut settings, files)| apply_flags(&cli, &mut settings).map(|_| (settings, files)));
+    let (settings, config_files) = match loaded {
+        Ok(loaded) => loaded,
+        Err(msg) => {
+            eprintln!("{}", to_error(&msg));
+            std::process::exit(2)
+        }
+    };
+    if let Err(msg) = logger::init(settings.log_level, &settings.log) {
+        eprintln!("{}", to_error(&msg));
+        std::process::exit(2)
+    }
+    text_coloring::set_color_mode(settings.output.color);
+    match config_files.as_slice() {
+        [] => debug!("No config file found, using defaults"),
+        files => files.iter().for_each(|f| debug!("Loaded config from {}", f.display())),
+    }
+
+    if cli.profile.is_some() && cli.command.is_some() {
+        eprintln!("{}", to_error("`--profile` is for the interactive mode"));
+        std::process::exit(2)
+    }
+    if let Some(sub_command) = cli.command {
+        match run_sub_command(sub_command, &settings) {
+            Ok(true) => std::process::exit(0),
+            Ok(false) => std::process::exit(1),
+            Err(msg) => {
+                eprintln!("{}", to_error(&msg));
+                std::process::exit(2)
+            }
+        }
+    }
 
     let mut command: Commands = Commands::UpdateHost;
     let mut service_request = ServiceRequest::default();
+    service_request.update_timeouts(settings.timeouts);
+    service_request.update_retry_policy(settings.retry.clone());
+    service_request.update_network(settings.network());
     let mut user_input = UserInput::empty();
+    let mut session = Session::new(settings.output.format);
+    if let Some(name) = &cli.profile {
+        match settings.profiles.get(name) {
+            Some(profile) => command = apply_profile(profile, &mut service_request),
+            None => {
+                let names = settings.profiles.keys().cloned().collect::<Vec<String>>().join(", ");
+                eprintln!("{}", to_error(&format!("Unknown profile `{}`. Profiles: {}", name, names)));
+                std::process::exit(2)
+            }
+        }
+    }
     let mut continuous_error_count: u8 = 0;
 
     loop {
@@ -134,7 +425,7 @@ fn main() {
                 error!("Exiting after failing 10 consecutive times");
                 command.set(Commands::Exit)
             }
-            if let Err(msg) = handle_command(&mut command, &mut service_request, &mut user_input) {
+            if let Err(msg) = handle_command(&mut command, &mut service_request, &mut user_input, &mut session, &settings) {
                 eprintln!("Failed while handling command `{}`", msg);
                 continuous_error_count += 1
             } else {
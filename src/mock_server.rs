@@ -0,0 +1,290 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor, MethodDescriptor, SerializeOptions};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio_stream::Stream;
+use tonic::metadata::MetadataMap;
+use tonic::{Request, Response, Status, Streaming};
+use crate::body_skeleton::skeleton_of;
+use crate::collection;
+use crate::descriptors;
+use crate::grpc_client;
+use crate::grpc_server::{self, path_of};
+use crate::grpc_request_dsl::{Network, ServiceRequest, Timeouts};
+use crate::grpc_status::StatusCode;
+use crate::text_coloring::{to_success, to_warn};
+use crate::util::parse_duration;
+
+// A line of the rules file. ex)
+// {"method": "helloworld.Greeter/SayHello", "match": {"name": "John"}, "body": {"message": "Hi John"}, "delay": "200ms"}
+// {"method": "helloworld.Greeter/SayHello", "status": {"code": "NOT_FOUND", "message": "no such user"}, "metadata": {"x-trace": "1"}}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MockRule {
+    pub method: String,
+    // fields every matching request has. Any request message of a stream can match
+    #[serde(default, rename = "match", skip_serializing_if = "Option::is_none")]
+    pub request: Option<Value>,
+    // a message, or an array of messages for server streaming
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<MockStatus>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delay: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MockStatus {
+    pub code: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub message: String,
+}
+
+// a rule checked against the schema
+#[derive(Debug, Clone)]
+struct Rule {
+    path: String,
+    request: Option<Value>,
+    messages: Vec<DynamicMessage>,
+    status: Option<(StatusCode, String)>,
+    metadata: MetadataMap,
+    delay: Option<Duration>,
+}
+
+impl Rule {
+    fn compile(rule: &MockRule, pool: &DescriptorPool) -> Result<Rule, String> {
+        let (service, function) = ServiceRequest::parse_method(&rule.method)?;
+        let method = descriptors::find_method(pool, &service, &function)?;
+        let status = rule.status.as_ref()
+            .map(|s| StatusCode::from_name(&s.code).map(|code| (code, s.message.clone())))
+            .transpose()?;
+        let bodies = match &rule.body {
+            // a successful call still has a message, as unary clients expect one
+            None if status.as_ref().is_none_or(|(code, _)| *code == StatusCode::Ok) => vec![skeleton_of(&method.output())],
+            None => vec![],
+            Some(Value::Array(messages)) if method.is_server_streaming() => messages.clone(),
+            Some(Value::Array(_)) => return Err(format!("`{}` responds a single message, not an array", rule.method)),
+            Some(body) => vec![body.clone()],
+        };
+        let messages = bodies.iter()
+            .map(|b| DynamicMessage::deserialize(method.output(), b).map_err(|e| format!("Invalid body for `{}`: {}", rule.method, e)))
+            .collect::<Result<Vec<DynamicMessage>, String>>()?;
+        let request = rule.request.as_ref()
+            .map(|pattern| pattern_of(pattern, &method.input()).map_err(|e| format!("Invalid `match` for `{}`: {}", rule.method, e)))
+            .transpose()?;
+        let mut metadata = MetadataMap::new();
+        grpc_client::add_metadata(&mut metadata, &rule.metadata).map_err(|e| e.message().to_string())?;
+        let delay = rule.delay.as_ref().map(|d| parse_duration(d)).transpose()?;
+        Ok(Rule { path: path_of(&method), request, messages, status, metadata, delay })
+    }
+
+    fn applies_to(&self, path: &str, requests: &[Value]) -> bool {
+        self.path == path && self.request.as_ref().is_none_or(|pattern| requests.iter().any(|r| matches(pattern, r)))
+    }
+}
+
+// `pattern` with its fields, given by proto or JSON name, named as requests are serialized
+fn pattern_of(pattern: &Value, message: &MessageDescriptor) -> Result<Value, String> {
+    let Value::Object(fields) = pattern else { return Ok(pattern.clone()) };
+    fields.iter()
+        .map(|(key, value)| {
+            let field = message.get_field_by_name(key).or_else(|| message.get_field_by_json_name(key))
+                .ok_or_else(|| format!("Unknown field `{}` of `{}`", key, message.full_name()))?;
+            let value = match field.kind().as_message() {
+                Some(nested) if !field.is_list() && !field.is_map() => pattern_of(value, nested)?,
+                _ => value.clone(),
+            };
+            Ok((String::from(field.json_name()), value))
+        })
+        .collect::<Result<serde_json::Map<String, Value>, String>>()
+        .map(Value::Object)
+}
+
+// a request as `match` sees it, with fields of default values as well
+fn request_value(message: &DynamicMessage) -> Result<Value, serde_json::Error> {
+    message.serialize_with_options(serde_json::value::Serializer, &SerializeOptions::new().skip_default_fields(false))
+}
+
+// every field of `pattern` is in `actual` with the same value.
+// Numbers match numbers in strings, as 64 bit integers are, and `1` matches `1.0`
+pub fn matches(pattern: &Value, actual: &Value) -> bool {
+    fn number_of(value: &Value) -> Option<(String, f64)> {
+        match value {
+            Value::Number(n) => n.as_f64().map(|f| (n.to_string(), f)),
+            Value::String(s) => s.parse::<f64>().ok().map(|f| (s.clone(), f)),
+            _ => None,
+        }
+    }
+    match (pattern, actual) {
+        (Value::Object(expected), Value::Object(fields)) =>
+            expected.iter().all(|(key, value)| fields.get(key).is_some_and(|field| matches(value, field))),
+        (Value::Number(_), _) | (_, Value::Number(_)) => pattern == actual || number_of(pattern).zip(number_of(actual))
+            .is_some_and(|((pattern_text, pattern_number), (text, number))| pattern_text == text || pattern_number == number),
+        _ => pattern == actual,
+    }
+}
+
+type ResponseStream = Pin<Box<dyn Stream<Item = Result<DynamicMessage, Status>> + Send>>;
+
+#[derive(Clone)]
+struct MockMethod {
+    method: MethodDescriptor,
+    rules: Arc<Vec<Rule>>,
+}
+
+impl tonic::server::StreamingService<DynamicMessage> for MockMethod {
+    type Response = DynamicMessage;
+    type ResponseStream = ResponseStream;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<ResponseStream>, Status>> + Send>>;
+
+    fn call(&mut self, request: Request<Streaming<DynamicMessage>>) -> Self::Future {
+        let (method, rules) = (self.method.clone(), self.rules.clone());
+        Box::pin(async move {
+            let mut stream = request.into_inner();
+            let mut requests = vec![];
+            while let Some(message) = stream.message().await? {
+                requests.push(request_value(&message).map_err(|e| Status::internal(e.to_string()))?);
+            }
+            let path = path_of(&method);
+            let rule = rules.iter().enumerate().find(|(_, r)| r.applies_to(&path, &requests));
+            let (rule, source) = match rule {
+                Some((i, rule)) => (rule.clone(), format!("rule #{}", i + 1)),
+                None => (default_rule(&method)?, String::from("default")),
+            };
+            if let Some(delay) = rule.delay {
+                tokio::time::sleep(delay).await;
+            }
+            match rule.status {
+                Some((code, message)) if code != StatusCode::Ok => {
+                    println!("{} {} ({})", path, to_warn(&code.to_string()), source);
+                    let mut status = Status::new(tonic::Code::from(code.code()), message);
+                    *status.metadata_mut() = rule.metadata;
+                    Err(status)
+                }
+                _ => {
+                    println!("{} {} ({})", path, to_success(&StatusCode::Ok.to_string()), source);
+                    let stream: ResponseStream = Box::pin(tokio_stream::iter(rule.messages.into_iter().map(Ok)));
+                    let mut response = Response::new(stream);
+                    *response.metadata_mut() = rule.metadata;
+                    Ok(response)
+                }
+            }
+        })
+    }
+}
+
+// a response with every field filled in
+fn default_rule(method: &MethodDescriptor) -> Result<Rule, Status> {
+    let message = DynamicMessage::deserialize(method.output(), &skeleton_of(&method.output()))
+        .map_err(|e| Status::internal(format!("Failed to generate a response: {}", e)))?;
+    Ok(Rule { path: path_of(method), request: None, messages: vec![message], status: None, metadata: MetadataMap::new(), delay: None })
+}
+
+pub fn run(source: &str, host: &str, port: u16, rules_path: Option<String>, timeouts: Timeouts, network: &Network) -> Result<bool, String> {
+    let pool = descriptors::pool_from_source(source, &timeouts, network)?;
+    let rules = match rules_path {
+        Some(path) => collection::load_jsonl::<MockRule>(&path)?.iter()
+            .map(|r| Rule::compile(r, &pool))
+            .collect::<Result<Vec<Rule>, String>>()?,
+        None => vec![],
+    };
+    println!("Mocking {} method(s) with {} rule(s) on {}:{}", grpc_server::methods_of(&pool).len(), rules.len(), host, port);
+    let rules = Arc::new(rules);
+    grpc_server::serve(host, port, &pool, move |method| MockMethod { method, rules: rules.clone() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn partial_match_of_request() {
+        let request = json!({"name": "John", "address": {"city": "Seoul", "zip": "04524"}, "tags": ["a"]});
+        assert!(matches(&json!({}), &request));
+        assert!(matches(&json!({"name": "John"}), &request));
+        assert!(matches(&json!({"address": {"city": "Seoul"}}), &request));
+        assert!(!matches(&json!({"address": {"city": "Busan"}}), &request));
+        assert!(!matches(&json!({"tags": []}), &request));
+        assert!(!matches(&json!({"age": 3}), &request));
+    }
+
+    fn users_pool() -> DescriptorPool {
+        use prost_types::field_descriptor_proto::{Label, Type};
+        use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet, MethodDescriptorProto, ServiceDescriptorProto};
+        let field = |name: &str, number: i32, r#type: Type, type_name: Option<&str>| FieldDescriptorProto {
+            name: Some(String::from(name)),
+            number: Some(number),
+            label: Some(Label::Optional as i32),
+            r#type: Some(r#type as i32),
+            type_name: type_name.map(String::from),
+            ..Default::default()
+        };
+        let message = |name: &str, field: Vec<FieldDescriptorProto>| DescriptorProto { name: Some(String::from(name)), field, ..Default::default() };
+        let file = FileDescriptorProto {
+            name: Some(String::from("users.proto")),
+            package: Some(String::from("users")),
+            syntax: Some(String::from("proto3")),
+            message_type: vec![
+                message("GetUser", vec![
+                    field("user_id", 1, Type::Int64, None),
+                    field("active", 2, Type::Bool, None),
+                    field("address", 3, Type::Message, Some(".users.Address")),
+                ]),
+                message("Address", vec![field("zip_code", 1, Type::String, None)]),
+            ],
+            service: vec![ServiceDescriptorProto {
+                name: Some(String::from("Users")),
+                method: vec![MethodDescriptorProto {
+                    name: Some(String::from("Get")),
+                    input_type: Some(String::from(".users.GetUser")),
+                    output_type: Some(String::from(".users.Address")),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        DescriptorPool::from_file_descriptor_set(FileDescriptorSet { file: vec![file] }).unwrap()
+    }
+
+    #[test]
+    fn match_decoded_requests() {
+        use prost::Message;
+        let pool = users_pool();
+        let input = pool.get_message_by_name("users.GetUser").unwrap();
+        let sent = DynamicMessage::deserialize(input.clone(), &json!({"userId": "42", "address": {"zipCode": "04524"}})).unwrap();
+        let received = DynamicMessage::decode(input, sent.encode_to_vec().as_slice()).unwrap();
+        let requests = vec![request_value(&received).unwrap()];
+
+        let rule = |pattern: Value| Rule::compile(&serde_json::from_value(json!({"method": "users.Users/Get", "match": pattern})).unwrap(), &pool);
+        for pattern in [json!({"user_id": 42}), json!({"userId": "42"}), json!({"active": false}), json!({"address": {"zip_code": "04524"}})] {
+            assert!(rule(pattern.clone()).unwrap().applies_to("/users.Users/Get", &requests), "{}", pattern);
+        }
+        assert!(!rule(json!({"user_id": 43})).unwrap().applies_to("/users.Users/Get", &requests));
+        assert!(rule(json!({"name": "a"})).unwrap_err().contains("Unknown field `name` of `users.GetUser`"));
+    }
+
+    #[test]
+    fn successful_rule_without_body_answers_a_message() {
+        let pool = users_pool();
+        let rule = |r: Value| Rule::compile(&serde_json::from_value(r).unwrap(), &pool).unwrap();
+        assert_eq!(rule(json!({"method": "users.Users/Get", "delay": "2s"})).messages.len(), 1);
+        assert_eq!(rule(json!({"method": "users.Users/Get", "status": {"code": "OK"}})).messages.len(), 1);
+        assert!(rule(json!({"method": "users.Users/Get", "status": {"code": "NOT_FOUND"}})).messages.is_empty());
+    }
+
+    #[test]
+    fn parse_rules() {
+        let rule: MockRule = serde_json::from_str(r#"{"method": "a.B/C", "match": {"id": 1}, "status": {"code": "NOT_FOUND"}, "delay": "1s"}"#).unwrap();
+        assert_eq!(rule.request, Some(json!({"id": 1})));
+        assert_eq!(rule.status.map(|s| s.code), Some(String::from("NOT_FOUND")));
+        assert!(rule.metadata.is_empty() && rule.body.is_none());
+    }
+}
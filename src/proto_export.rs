@@ -0,0 +1,454 @@
+use std::collections::HashMap;
+use std::path::Path;
+use prost_reflect::DescriptorPool;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FileDescriptorProto, ServiceDescriptorProto};
+use crate::descriptors;
+use crate::grpc_request_dsl::{Network, Timeouts};
+use crate::text_coloring::to_success;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ExportFormat {
+    Proto,
+    Protoset,
+}
+
+impl ExportFormat {
+    pub fn from(s: &str) -> Result<ExportFormat, String> {
+        match s.to_lowercase().as_str() {
+            "proto" => Ok(ExportFormat::Proto),
+            "protoset" => Ok(ExportFormat::Protoset),
+            _ => Err(format!("Invalid export format `{}`. Expected one of proto, protoset", s)),
+        }
+    }
+}
+
+// field numbers of FileDescriptorProto and its children, used by `SourceCodeInfo` paths
+const FILE_MESSAGE: i32 = 4;
+const FILE_ENUM: i32 = 5;
+const FILE_SERVICE: i32 = 6;
+const MESSAGE_FIELD: i32 = 2;
+const MESSAGE_NESTED: i32 = 3;
+const MESSAGE_ENUM: i32 = 4;
+const ENUM_VALUE: i32 = 2;
+const SERVICE_METHOD: i32 = 2;
+
+pub fn run(source: &str, format: ExportFormat, out: Option<String>, timeouts: Timeouts, network: &Network) -> Result<bool, String> {
+    let pool = descriptors::pool_from_source(source, &timeouts, network)?;
+    match format {
+        ExportFormat::Protoset => {
+            let out = out.unwrap_or_else(|| String::from("schema.protoset"));
+            std::fs::write(&out, pool.encode_to_vec()).map_err(|e| format!("Failed to write `{}`: {}", out, e))?;
+            println!("{} {}", to_success("WROTE"), out);
+        }
+        ExportFormat::Proto => {
+            let out = out.unwrap_or_else(|| String::from("proto"));
+            for file in exported_files(&pool) {
+                let path = Path::new(&out).join(file.name());
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create `{}`: {}", dir.display(), e))?;
+                }
+                std::fs::write(&path, to_proto(file)).map_err(|e| format!("Failed to write `{}`: {}", path.display(), e))?;
+                println!("{} {}", to_success("WROTE"), path.display());
+            }
+        }
+    }
+    Ok(true)
+}
+
+// well known types ship with every protobuf distribution
+fn exported_files(pool: &DescriptorPool) -> impl Iterator<Item = &FileDescriptorProto> {
+    pool.file_descriptor_protos().filter(|f| !f.name().starts_with("google/protobuf/"))
+}
+
+struct Writer<'a> {
+    out: String,
+    package: String,
+    comments: HashMap<Vec<i32>, String>,
+    file: &'a FileDescriptorProto,
+}
+
+impl Writer<'_> {
+    fn line(&mut self, depth: usize, s: &str) {
+        self.out.push_str(&"  ".repeat(depth));
+        self.out.push_str(s);
+        self.out.push('\n');
+    }
+
+    fn comment(&mut self, depth: usize, path: &[i32]) {
+        if let Some(comment) = self.comments.get(path).cloned() {
+            comment.trim_end().lines().for_each(|l| self.line(depth, &format!("//{}", l)));
+        }
+    }
+
+    // relative to the package when possible. ex) `.helloworld.HelloRequest` in `helloworld` is `HelloRequest`
+    fn type_name(&self, name: &str) -> String {
+        let name = name.trim_start_matches('.');
+        match name.strip_prefix(&format!("{}.", self.package)) {
+            Some(relative) if !self.package.is_empty() => String::from(relative),
+            _ => String::from(name),
+        }
+    }
+
+    fn is_proto3(&self) -> bool {
+        self.file.syntax() == "proto3"
+    }
+}
+
+pub fn to_proto(file: &FileDescriptorProto) -> String {
+    let comments = file.source_code_info.iter()
+        .flat_map(|info| info.location.iter())
+        .filter_map(|l| l.leading_comments.clone().map(|c| (l.path.clone(), c)))
+        .collect();
+    let mut w = Writer { out: String::new(), package: String::from(file.package()), comments, file };
+
+    w.line(0, "// Reconstructed by zrpc-cli from server reflection");
+    match file.syntax() {
+        "" => w.line(0, "syntax = \"proto2\";"),
+        // the edition itself is not exposed by prost-types
+        "editions" => w.line(0, "edition = \"2023\";"),
+        syntax => w.line(0, &format!("syntax = \"{}\";", syntax)),
+    }
+    if !w.package.is_empty() {
+        w.line(0, "");
+        w.line(0, &format!("package {};", w.package));
+    }
+    if !file.dependency.is_empty() {
+        w.line(0, "");
+        for (i, dependency) in file.dependency.iter().enumerate() {
+            let modifier = if file.public_dependency.contains(&(i as i32)) { "public " } else if file.weak_dependency.contains(&(i as i32)) { "weak " } else { "" };
+            w.line(0, &format!("import {}\"{}\";", modifier, dependency));
+        }
+    }
+    let options = file_options(file);
+    if !options.is_empty() {
+        w.line(0, "");
+        options.iter().for_each(|o| w.line(0, &format!("option {};", o)));
+    }
+    for (i, service) in file.service.iter().enumerate() {
+        w.line(0, "");
+        write_service(&mut w, service, &[FILE_SERVICE, i as i32]);
+    }
+    for (i, message) in file.message_type.iter().enumerate() {
+        w.line(0, "");
+        write_message(&mut w, message, 0, &[FILE_MESSAGE, i as i32]);
+    }
+    for (i, enumeration) in file.enum_type.iter().enumerate() {
+        w.line(0, "");
+        write_enum(&mut w, enumeration, 0, &[FILE_ENUM, i as i32]);
+    }
+    if !file.extension.is_empty() {
+        w.line(0, "");
+        write_extensions(&mut w, &file.extension, 0);
+    }
+    w.out
+}
+
+fn file_options(file: &FileDescriptorProto) -> Vec<String> {
+    let o = match &file.options {
+        Some(o) => o,
+        None => return vec![],
+    };
+    let quoted = |name: &str, value: &Option<String>| value.as_ref().map(|v| format!("{} = \"{}\"", name, escape(v)));
+    let flag = |name: &str, value: Option<bool>| value.map(|v| format!("{} = {}", name, v));
+    [
+        quoted("java_package", &o.java_package),
+        quoted("java_outer_classname", &o.java_outer_classname),
+        flag("java_multiple_files", o.java_multiple_files),
+        quoted("go_package", &o.go_package),
+        quoted("csharp_namespace", &o.csharp_namespace),
+        quoted("objc_class_prefix", &o.objc_class_prefix),
+        quoted("php_namespace", &o.php_namespace),
+        quoted("ruby_package", &o.ruby_package),
+        quoted("swift_prefix", &o.swift_prefix),
+        o.optimize_for.map(|_| format!("optimize_for = {}", o.optimize_for().as_str_name())),
+        flag("cc_enable_arenas", o.cc_enable_arenas),
+        flag("deprecated", o.deprecated),
+    ].into_iter().flatten().collect()
+}
+
+fn write_service(w: &mut Writer, service: &ServiceDescriptorProto, path: &[i32]) {
+    w.comment(0, path);
+    w.line(0, &format!("service {} {{", service.name()));
+    if service.options.as_ref().and_then(|o| o.deprecated) == Some(true) {
+        w.line(1, "option deprecated = true;");
+    }
+    for (i, method) in service.method.iter().enumerate() {
+        w.comment(1, &[path, &[SERVICE_METHOD, i as i32]].concat());
+        let stream = |streaming: bool| if streaming { "stream " } else { "" };
+        let signature = format!("rpc {}({}{}) returns ({}{})", method.name(),
+                                stream(method.client_streaming()), w.type_name(method.input_type()),
+                                stream(method.server_streaming()), w.type_name(method.output_type()));
+        match method.options.as_ref().and_then(|o| o.deprecated) {
+            Some(true) => {
+                w.line(1, &format!("{} {{", signature));
+                w.line(2, "option deprecated = true;");
+                w.line(1, "}");
+            }
+            _ => w.line(1, &format!("{};", signature)),
+        }
+    }
+    w.line(0, "}");
+}
+
+fn write_message(w: &mut Writer, message: &DescriptorProto, depth: usize, path: &[i32]) {
+    w.comment(depth, path);
+    w.line(depth, &format!("message {} {{", message.name()));
+    if message.options.as_ref().and_then(|o| o.deprecated) == Some(true) {
+        w.line(depth + 1, "option deprecated = true;");
+    }
+    write_reserved(w, depth + 1,
+                   message.reserved_range.iter().map(|r| (r.start(), r.end() - 1)).collect(),
+                   &message.reserved_name);
+
+    let map_entries: HashMap<String, &DescriptorProto> = message.nested_type.iter()
+        .filter(|n| n.options.as_ref().and_then(|o| o.map_entry) == Some(true))
+        .map(|n| (n.name().to_string(), n))
+        .collect();
+    let mut written_oneofs = vec![];
+    for (i, field) in message.field.iter().enumerate() {
+        let field_path = [path, &[MESSAGE_FIELD, i as i32]].concat();
+        match field.oneof_index {
+            // proto3 `optional` is a oneof of its own, written as `optional`
+            Some(index) if !field.proto3_optional() => {
+                if written_oneofs.contains(&index) { continue; }
+                written_oneofs.push(index);
+                w.line(depth + 1, &format!("oneof {} {{", message.oneof_decl[index as usize].name()));
+                for (j, member) in message.field.iter().enumerate().filter(|(_, f)| f.oneof_index == Some(index)) {
+                    w.comment(depth + 2, &[path, &[MESSAGE_FIELD, j as i32]].concat());
+                    let line = field_line(w, member, &map_entries);
+                    w.line(depth + 2, &line);
+                }
+                w.line(depth + 1, "}");
+            }
+            _ => {
+                w.comment(depth + 1, &field_path);
+                let line = field_line(w, field, &map_entries);
+                w.line(depth + 1, &line);
+            }
+        }
+    }
+    for (i, nested) in message.nested_type.iter().enumerate() {
+        if !map_entries.contains_key(nested.name()) {
+            write_message(w, nested, depth + 1, &[path, &[MESSAGE_NESTED, i as i32]].concat());
+        }
+    }
+    for (i, enumeration) in message.enum_type.iter().enumerate() {
+        write_enum(w, enumeration, depth + 1, &[path, &[MESSAGE_ENUM, i as i32]].concat());
+    }
+    if !message.extension.is_empty() {
+        write_extensions(w, &message.extension, depth + 1);
+    }
+    w.line(depth, "}");
+}
+
+fn field_line(w: &Writer, field: &FieldDescriptorProto, map_entries: &HashMap<String, &DescriptorProto>) -> String {
+    let map_entry = field.type_name.as_ref()
+        .and_then(|t| t.rsplit('.').next())
+        .and_then(|name| map_entries.get(name))
+        .filter(|_| field.label() == Label::Repeated);
+    let declaration = match map_entry {
+        Some(entry) => format!("map<{}, {}>", type_of(w, &entry.field[0]), type_of(w, &entry.field[1])),
+        None => {
+            let label = match field.label() {
+                Label::Repeated => "repeated ",
+                Label::Required => "required ",
+                Label::Optional if field.proto3_optional() => "optional ",
+                // implicit in proto3 and within a oneof
+                Label::Optional if w.is_proto3() || field.oneof_index.is_some() => "",
+                Label::Optional => "optional ",
+            };
+            format!("{}{}", label, type_of(w, field))
+        }
+    };
+    let mut options = vec![];
+    if let Some(default) = &field.default_value {
+        options.push(match field.r#type() {
+            Type::String => format!("default = \"{}\"", escape(default)),
+            Type::Bytes => format!("default = \"{}\"", default),
+            _ => format!("default = {}", default),
+        });
+    }
+    if field.json_name.as_ref().is_some_and(|j| *j != json_name_of(field.name())) {
+        options.push(format!("json_name = \"{}\"", field.json_name()));
+    }
+    if let Some(o) = &field.options {
+        if let Some(packed) = o.packed { options.push(format!("packed = {}", packed)); }
+        if o.deprecated == Some(true) { options.push(String::from("deprecated = true")); }
+    }
+    let options = if options.is_empty() { String::new() } else { format!(" [{}]", options.join(", ")) };
+    format!("{} {} = {}{};", declaration, field.name(), field.number(), options)
+}
+
+fn type_of(w: &Writer, field: &FieldDescriptorProto) -> String {
+    match field.r#type() {
+        Type::Message | Type::Enum | Type::Group => w.type_name(field.type_name()),
+        scalar => scalar.as_str_name().trim_start_matches("TYPE_").to_lowercase(),
+    }
+}
+
+fn write_enum(w: &mut Writer, enumeration: &EnumDescriptorProto, depth: usize, path: &[i32]) {
+    w.comment(depth, path);
+    w.line(depth, &format!("enum {} {{", enumeration.name()));
+    if let Some(o) = &enumeration.options {
+        if o.allow_alias == Some(true) { w.line(depth + 1, "option allow_alias = true;"); }
+        if o.deprecated == Some(true) { w.line(depth + 1, "option deprecated = true;"); }
+    }
+    write_reserved(w, depth + 1,
+                   enumeration.reserved_range.iter().map(|r| (r.start(), r.end())).collect(),
+                   &enumeration.reserved_name);
+    for (i, value) in enumeration.value.iter().enumerate() {
+        w.comment(depth + 1, &[path, &[ENUM_VALUE, i as i32]].concat());
+        let deprecated = if value.options.as_ref().and_then(|o| o.deprecated) == Some(true) { " [deprecated = true]" } else { "" };
+        w.line(depth + 1, &format!("{} = {}{};", value.name(), value.number(), deprecated));
+    }
+    w.line(depth, "}");
+}
+
+fn write_extensions(w: &mut Writer, extensions: &[FieldDescriptorProto], depth: usize) {
+    for extension in extensions {
+        w.line(depth, &format!("extend {} {{", w.type_name(extension.extendee())));
+        let line = field_line(w, extension, &HashMap::new());
+        w.line(depth + 1, &line);
+        w.line(depth, "}");
+    }
+}
+
+// inclusive ranges
+fn write_reserved(w: &mut Writer, depth: usize, ranges: Vec<(i32, i32)>, names: &[String]) {
+    if !ranges.is_empty() {
+        let ranges: Vec<String> = ranges.iter().map(|(start, end)| match end {
+            end if end == start => start.to_string(),
+            &i32::MAX | &536_870_911 => format!("{} to max", start),
+            end => format!("{} to {}", start, end),
+        }).collect();
+        w.line(depth, &format!("reserved {};", ranges.join(", ")));
+    }
+    if !names.is_empty() {
+        let names: Vec<String> = names.iter().map(|n| format!("\"{}\"", n)).collect();
+        w.line(depth, &format!("reserved {};", names.join(", ")));
+    }
+}
+
+// what protoc derives when `json_name` is not given
+fn json_name_of(name: &str) -> String {
+    let mut json_name = String::new();
+    let mut upper_next = false;
+    for c in name.chars() {
+        match c {
+            '_' => upper_next = true,
+            c if upper_next => {
+                json_name.extend(c.to_uppercase());
+                upper_next = false;
+            }
+            c => json_name.push(c),
+        }
+    }
+    json_name
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_types::{MethodDescriptorProto, OneofDescriptorProto, SourceCodeInfo, source_code_info::Location, MessageOptions, FileOptions};
+
+    fn field(name: &str, number: i32, r#type: Type, label: Label, type_name: Option<&str>) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(String::from(name)),
+            number: Some(number),
+            label: Some(label as i32),
+            r#type: Some(r#type as i32),
+            type_name: type_name.map(String::from),
+            json_name: Some(json_name_of(name)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reconstructs_proto_source() {
+        let entry = DescriptorProto {
+            name: Some(String::from("LabelsEntry")),
+            field: vec![field("key", 1, Type::String, Label::Optional, None), field("value", 2, Type::String, Label::Optional, None)],
+            options: Some(MessageOptions { map_entry: Some(true), ..Default::default() }),
+            ..Default::default()
+        };
+        let mut nickname = field("nickname", 4, Type::String, Label::Optional, None);
+        nickname.oneof_index = Some(1);
+        nickname.proto3_optional = Some(true);
+        let mut email = field("email", 5, Type::String, Label::Optional, None);
+        email.oneof_index = Some(0);
+        let file = FileDescriptorProto {
+            name: Some(String::from("hello.proto")),
+            package: Some(String::from("hello")),
+            syntax: Some(String::from("proto3")),
+            dependency: vec![String::from("google/protobuf/timestamp.proto")],
+            options: Some(FileOptions { go_package: Some(String::from("example.com/hello")), ..Default::default() }),
+            service: vec![ServiceDescriptorProto {
+                name: Some(String::from("Greeter")),
+                method: vec![MethodDescriptorProto {
+                    name: Some(String::from("SayHello")),
+                    input_type: Some(String::from(".hello.HelloRequest")),
+                    output_type: Some(String::from(".hello.HelloRequest")),
+                    server_streaming: Some(true),
+                    ..Default::default()
+                }],
+                options: None,
+            }],
+            message_type: vec![DescriptorProto {
+                name: Some(String::from("HelloRequest")),
+                field: vec![
+                    field("user_name", 1, Type::String, Label::Optional, None),
+                    field("labels", 2, Type::Message, Label::Repeated, Some(".hello.HelloRequest.LabelsEntry")),
+                    field("sent_at", 3, Type::Message, Label::Optional, Some(".google.protobuf.Timestamp")),
+                    nickname,
+                    email,
+                ],
+                nested_type: vec![entry],
+                oneof_decl: vec![
+                    OneofDescriptorProto { name: Some(String::from("contact")), options: None },
+                    OneofDescriptorProto { name: Some(String::from("_nickname")), options: None },
+                ],
+                ..Default::default()
+            }],
+            source_code_info: Some(SourceCodeInfo {
+                location: vec![Location { path: vec![FILE_MESSAGE, 0, MESSAGE_FIELD, 0], leading_comments: Some(String::from(" who to greet\n")), ..Default::default() }],
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(to_proto(&file), r#"// Reconstructed by zrpc-cli from server reflection
+syntax = "proto3";
+
+package hello;
+
+import "google/protobuf/timestamp.proto";
+
+option go_package = "example.com/hello";
+
+service Greeter {
+  rpc SayHello(HelloRequest) returns (stream HelloRequest);
+}
+
+message HelloRequest {
+  // who to greet
+  string user_name = 1;
+  map<string, string> labels = 2;
+  google.protobuf.Timestamp sent_at = 3;
+  optional string nickname = 4;
+  oneof contact {
+    string email = 5;
+  }
+}
+"#);
+    }
+
+    #[test]
+    fn json_names() {
+        assert_eq!(json_name_of("user_name"), "userName");
+        assert_eq!(json_name_of("id"), "id");
+    }
+}
@@ -0,0 +1,100 @@
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use crate::grpc_request_dsl::{ServiceFunction, ServiceName, ServiceRequest, Target};
+use crate::util::state_dir;
+
+const MAX_RECENT_TARGETS: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecentTarget {
+    pub target: String,
+    pub service: String,
+    pub function: String,
+}
+
+impl RecentTarget {
+    pub fn apply_to(&self, service_request: &mut ServiceRequest) -> Result<(), String> {
+        service_request.update_target(Target::from(&self.target)?);
+        service_request.update_service(ServiceName::from(&self.service));
+        service_request.update_function(ServiceFunction::from(&self.function));
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for RecentTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}/{}", self.target, self.service, self.function)
+    }
+}
+
+// most recently used first, one entry per target
+#[derive(Debug, Clone, Default)]
+pub struct RecentTargets {
+    pub entries: Vec<RecentTarget>,
+}
+
+impl RecentTargets {
+    pub fn load() -> RecentTargets {
+        let entries = file_path().ok()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        RecentTargets { entries }
+    }
+
+    pub fn record(&mut self, service_request: &ServiceRequest) {
+        let recent = RecentTarget {
+            target: service_request.target().to_string(),
+            service: service_request.service_name.0.clone(),
+            function: service_request.service_function.0.clone(),
+        };
+        self.entries.retain(|e| e.target != recent.target);
+        self.entries.insert(0, recent);
+        self.entries.truncate(MAX_RECENT_TARGETS);
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = file_path()?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create `{}`: {}", dir.display(), e))?;
+        }
+        let json = serde_json::to_string_pretty(&self.entries).map_err(|e| e.to_string())?;
+        std::fs::write(&path, json).map_err(|e| format!("Failed to save recent targets to `{}`: {}", path.display(), e))
+    }
+}
+
+fn file_path() -> Result<PathBuf, String> {
+    state_dir().map(|d| d.join("recent_targets.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc_request_dsl::Network;
+
+    fn request(target: &str, function: &str) -> ServiceRequest {
+        ServiceRequest::parse(target, &format!("helloworld.Greeter/{}", function), Network::default()).unwrap()
+    }
+
+    #[test]
+    fn most_recent_first_without_duplicates() {
+        let mut recent = RecentTargets::default();
+        recent.record(&request("localhost:50051", "SayHello"));
+        recent.record(&request("api:8443", "SayHello"));
+        recent.record(&request("localhost:50051", "SayBye"));
+        let targets: Vec<String> = recent.entries.iter().map(|e| e.to_string()).collect();
+        assert_eq!(targets, vec!["grpc://localhost:50051 helloworld.Greeter/SayBye", "grpc://api:8443 helloworld.Greeter/SayHello"]);
+
+        (0..20).for_each(|i| recent.record(&request(&format!("host{}:1", i), "SayHello")));
+        assert_eq!(recent.entries.len(), MAX_RECENT_TARGETS);
+        assert_eq!(recent.entries[0].target, "grpc://host19:1");
+    }
+
+    #[test]
+    fn resume_from_recent_target() {
+        let recent = RecentTarget { target: String::from("grpcs://api:443"), service: String::from("helloworld.Greeter"), function: String::from("SayHello") };
+        let mut service_request = ServiceRequest::default();
+        recent.apply_to(&mut service_request).unwrap();
+        assert_eq!(service_request, request("grpcs://api:443", "SayHello"));
+    }
+}
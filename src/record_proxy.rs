@@ -0,0 +1,297 @@
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::Write;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
+use std::time::Instant;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use prost_reflect::{DynamicMessage, MethodDescriptor};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::metadata::{KeyAndValueRef, MetadataMap};
+use tonic::transport::Channel;
+use tonic::{Request, Response, Status, Streaming};
+use crate::collection::{self, CollectionEntry};
+use crate::descriptors;
+use crate::dynamic_codec::DynamicCodec;
+use crate::grpc_client;
+use crate::grpc_request_dsl::{Network, SchemaFiles, Target, Timeouts};
+use crate::grpc_server::{self, path_of};
+use crate::grpc_status::{GrpcStatus, StatusCode};
+use crate::latency::format_duration;
+use crate::mock_server::{MockRule, MockStatus};
+use crate::text_coloring::{to_success, to_warn};
+
+// appends each call to the collection, and to the mock rules when asked
+struct Recorder {
+    upstream: Target,
+    collection: Mutex<File>,
+    rules: Option<Mutex<File>>,
+    // entries are named after their line, so names stay unique across sessions
+    recorded: AtomicU64,
+}
+
+impl Recorder {
+    fn record(&self, call: &Call, responses: Vec<Value>, status: &Status, headers: &MetadataMap) {
+        let code = StatusCode::from(status.code() as i32);
+        let elapsed = call.started.elapsed();
+        let line = self.recorded.fetch_add(1, Ordering::SeqCst) + 1;
+        let requests = call.requests.lock().map(|r| r.clone()).unwrap_or_default();
+        let entry = CollectionEntry {
+            name: format!("{}-{}", call.method.name(), line),
+            host: self.upstream.host.to_string(),
+            port: self.upstream.port.as_ref().map_or(0, |p| p.0),
+            service: call.method.parent_service().full_name().to_string(),
+            function: call.method.name().to_string(),
+            tls: self.upstream.tls,
+            body: messages_of(requests.clone()).unwrap_or_else(|| Value::Object(serde_json::Map::new())),
+            metadata: metadata_of(&call.metadata),
+            schema: SchemaFiles::default(),
+            response: messages_of(responses.clone()),
+            status: Some(code.name().to_string()),
+            elapsed_ms: Some(elapsed.as_millis() as u64),
+        };
+        let rule = MockRule {
+            method: format!("{}/{}", entry.service, entry.function),
+            request: requests.first().cloned(),
+            body: entry.response.clone(),
+            status: (code != StatusCode::Ok).then(|| MockStatus { code: code.name().to_string(), message: status.message().to_string() }),
+            metadata: metadata_of(if code == StatusCode::Ok { headers } else { status.metadata() }),
+            delay: None,
+        };
+        let result = append(&self.collection, &entry)
+            .and_then(|_| self.rules.as_ref().map_or(Ok(()), |rules| append(rules, &rule)));
+        let code = if code == StatusCode::Ok { to_success(&code.to_string()) } else { to_warn(&code.to_string()) };
+        match result {
+            Ok(_) => println!("{} {} {}", path_of(&call.method), code, format_duration(elapsed)),
+            Err(e) => eprintln!("{} {} {}: {}", path_of(&call.method), code, format_duration(elapsed), e),
+        }
+    }
+}
+
+fn append<T: Serialize>(file: &Mutex<File>, value: &T) -> Result<(), String> {
+    let line = serde_json::to_string(value).map_err(|e| format!("Failed to write a record: {}", e))?;
+    let mut file = file.lock().map_err(|_| String::from("Failed to write a record"))?;
+    writeln!(file, "{}", line).map_err(|e| format!("Failed to write a record: {}", e))
+}
+
+fn open_append(path: &str) -> Result<File, String> {
+    OpenOptions::new().create(true).append(true).open(path)
+        .map_err(|e| format!("Failed to open `{}`: {}", path, e))
+}
+
+// a single message as it is, a stream as an array
+fn messages_of(mut messages: Vec<Value>) -> Option<Value> {
+    match messages.len() {
+        0 => None,
+        1 => messages.pop(),
+        _ => Some(Value::Array(messages)),
+    }
+}
+
+const TRANSPORT_HEADERS: [&str; 8] = ["te", "content-type", "content-length", "user-agent", "accept", "accept-encoding", "host", "date"];
+
+// what the caller sent, without the headers HTTP/2 and gRPC themselves use. Binary values in base64
+fn metadata_of(metadata: &MetadataMap) -> BTreeMap<String, String> {
+    metadata.iter()
+        .filter_map(|entry| match entry {
+            KeyAndValueRef::Ascii(key, value) => value.to_str().ok().map(|v| (key.as_str().to_string(), v.to_string())),
+            KeyAndValueRef::Binary(key, value) => value.to_bytes().ok().map(|v| (key.as_str().to_string(), STANDARD.encode(v))),
+        })
+        .filter(|(key, _)| !(key.starts_with("grpc-") || TRANSPORT_HEADERS.contains(&key.as_str())))
+        .collect()
+}
+
+// metadata passed through either way. `grpc-timeout` goes upstream as it is
+fn forwarded(metadata: &MetadataMap) -> MetadataMap {
+    let mut headers = metadata.clone().into_headers();
+    TRANSPORT_HEADERS.iter().for_each(|h| { headers.remove(*h); });
+    MetadataMap::from_headers(headers)
+}
+
+fn json_of(message: &DynamicMessage) -> Value {
+    serde_json::to_value(message).unwrap_or(Value::Null)
+}
+
+struct Call {
+    method: MethodDescriptor,
+    metadata: MetadataMap,
+    requests: Arc<Mutex<Vec<Value>>>,
+    started: Instant,
+}
+
+type ResponseStream = Pin<Box<dyn Stream<Item = Result<DynamicMessage, Status>> + Send>>;
+
+#[derive(Clone)]
+struct RecordMethod {
+    method: MethodDescriptor,
+    channel: Channel,
+    recorder: Arc<Recorder>,
+}
+
+impl tonic::server::StreamingService<DynamicMessage> for RecordMethod {
+    type Response = DynamicMessage;
+    type ResponseStream = ResponseStream;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<ResponseStream>, Status>> + Send>>;
+
+    fn call(&mut self, request: Request<Streaming<DynamicMessage>>) -> Self::Future {
+        let (method, channel, recorder) = (self.method.clone(), self.channel.clone(), self.recorder.clone());
+        Box::pin(async move {
+            let (metadata, _, incoming) = request.into_parts();
+            let call = Call { method: method.clone(), metadata: metadata.clone(), requests: Arc::new(Mutex::new(vec![])), started: Instant::now() };
+            // forwarded as they come, so streams stay interactive. An error of the client's stream ends the call
+            // instead of reaching the server as a half-close
+            let sent = call.requests.clone();
+            let (failed, failures) = tokio::sync::mpsc::channel(1);
+            let outgoing = incoming.map_while(move |m| match m {
+                Ok(m) => {
+                    if let Ok(mut sent) = sent.lock() {
+                        sent.push(json_of(&m));
+                    }
+                    Some(m)
+                }
+                Err(status) => {
+                    let _ = failed.try_send(status);
+                    None
+                }
+            });
+            let mut upstream = Request::new(outgoing);
+            *upstream.metadata_mut() = forwarded(&metadata);
+            let path = PathAndQuery::from_maybe_shared(path_of(&method)).map_err(|e| Status::internal(e.to_string()))?;
+
+            let mut grpc = tonic::client::Grpc::new(channel);
+            if let Err(e) = grpc.ready().await {
+                let status = Status::unavailable(e.to_string());
+                recorder.record(&call, vec![], &status, &MetadataMap::new());
+                return Err(status);
+            }
+            let (headers, stream, _) = match grpc.streaming(upstream, path, DynamicCodec::new(method.output())).await {
+                Ok(response) => response.into_parts(),
+                Err(status) => {
+                    recorder.record(&call, vec![], &status, &MetadataMap::new());
+                    return Err(status);
+                }
+            };
+            let (sender, receiver) = tokio::sync::mpsc::channel(16);
+            let recorded_headers = headers.clone();
+            tokio::spawn(async move {
+                let (responses, status) = relay(stream, failures, sender).await;
+                recorder.record(&call, responses, &status, &recorded_headers);
+            });
+            let stream: ResponseStream = Box::pin(ReceiverStream::new(receiver));
+            let mut response = Response::new(stream);
+            *response.metadata_mut() = forwarded(&headers);
+            Ok(response)
+        })
+    }
+}
+
+// passes the responses on to the client until the server or the client's stream ends the call, giving back what was passed and the status
+async fn relay<S>(mut upstream: S, mut failures: Receiver<Status>, sender: Sender<Result<DynamicMessage, Status>>) -> (Vec<Value>, Status)
+where
+    S: Stream<Item = Result<DynamicMessage, Status>> + Unpin,
+{
+    let mut responses = vec![];
+    let status = loop {
+        // a failure of the client comes first, as the server may answer the half-close it caused
+        let next = std::future::poll_fn(|cx| match failures.poll_recv(cx) {
+            Poll::Ready(Some(status)) => Poll::Ready(Some(Err(status))),
+            _ => Pin::new(&mut upstream).poll_next(cx),
+        }).await;
+        match next {
+            Some(Ok(message)) => {
+                responses.push(json_of(&message));
+                if sender.send(Ok(message)).await.is_err() {
+                    break Status::cancelled("The client went away");
+                }
+            }
+            Some(Err(status)) => {
+                let _ = sender.send(Err(status.clone())).await;
+                break status;
+            }
+            None => break Status::ok(""),
+        }
+    };
+    (responses, status)
+}
+
+pub fn run(listen: &str, upstream: &str, out: &str, rules: Option<String>, timeouts: Timeouts, network: &Network) -> Result<bool, String> {
+    let (host, port) = grpc_server::parse_listen(listen)?;
+    let upstream = Target::from_input(upstream, network.default_tls)?;
+    let pool = descriptors::pool_of(&upstream, &timeouts, network)?;
+    let channel = grpc_client::runtime().block_on(grpc_client::connect(&upstream, &timeouts, network))
+        .map_err(|e| GrpcStatus::from(&e).to_string())?;
+    let recorded = match std::path::Path::new(out).exists() {
+        true => collection::load(out)?.len() as u64,
+        false => 0,
+    };
+    let recorder = Arc::new(Recorder {
+        upstream: upstream.clone(),
+        collection: Mutex::new(open_append(out)?),
+        rules: rules.as_deref().map(open_append).transpose()?.map(Mutex::new),
+        recorded: AtomicU64::new(recorded),
+    });
+
+    println!("Recording calls to {} on {}:{} into {}", upstream, host, port, out);
+    grpc_server::serve(&host, port, &pool, move |method| RecordMethod { method, channel: channel.clone(), recorder: recorder.clone() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tonic::metadata::MetadataValue;
+
+    #[test]
+    fn streams_are_recorded_as_arrays() {
+        assert_eq!(messages_of(vec![]), None);
+        assert_eq!(messages_of(vec![json!({"a": 1})]), Some(json!({"a": 1})));
+        assert_eq!(messages_of(vec![json!({"a": 1}), json!({"a": 2})]), Some(json!([{"a": 1}, {"a": 2}])));
+    }
+
+    #[test]
+    fn grpc_headers_are_not_recorded() {
+        let mut metadata = MetadataMap::new();
+        metadata.insert("authorization", MetadataValue::from_static("Bearer token"));
+        metadata.insert("grpc-timeout", MetadataValue::from_static("1S"));
+        metadata.insert("te", MetadataValue::from_static("trailers"));
+        metadata.insert("content-length", MetadataValue::from_static("11"));
+        metadata.insert_bin("trace-bin", MetadataValue::from_bytes(&[1, 2]));
+        assert_eq!(metadata_of(&metadata), BTreeMap::from([
+            (String::from("authorization"), String::from("Bearer token")),
+            (String::from("trace-bin"), String::from("AQI=")),
+        ]));
+    }
+
+    #[test]
+    fn client_stream_errors_fail_the_call() {
+        let source_context = prost_reflect::DescriptorPool::global().get_message_by_name("google.protobuf.SourceContext").unwrap();
+        let message = |name: &str| DynamicMessage::deserialize(source_context.clone(), json!({"fileName": name})).unwrap();
+        grpc_client::runtime().block_on(async {
+            let (_failed, failures) = tokio::sync::mpsc::channel(1);
+            let (sender, mut receiver) = tokio::sync::mpsc::channel(16);
+            let upstream = tokio_stream::iter(vec![Ok(message("a")), Ok(message("b"))]);
+            let (responses, status) = relay(upstream, failures, sender).await;
+            assert_eq!((responses, status.code()), (vec![json!({"fileName": "a"}), json!({"fileName": "b"})], tonic::Code::Ok));
+            assert!(receiver.recv().await.unwrap().is_ok());
+
+            let (failed, failures) = tokio::sync::mpsc::channel(1);
+            failed.try_send(Status::internal("Failed to decode the request")).unwrap();
+            let (sender, mut receiver) = tokio::sync::mpsc::channel(16);
+            // what the server answers to the half-close isn't taken as the outcome
+            let upstream = tokio_stream::iter(vec![Ok(message("truncated"))]);
+            let (responses, status) = relay(upstream, failures, sender).await;
+            assert!(responses.is_empty());
+            assert_eq!((status.code(), status.message()), (tonic::Code::Internal, "Failed to decode the request"));
+            assert_eq!(receiver.recv().await.unwrap().unwrap_err().code(), tonic::Code::Internal);
+        });
+    }
+}
@@ -0,0 +1,505 @@
+use std::cmp::Ordering;
+use serde_json::{Map, Value};
+
+// A small subset of jq: paths(`.a.b`, `.[0]`, `.[]`), pipes, `,`, comparisons, `and`/`or`,
+// array and object construction, and `select`, `map`, `length`, `keys`, `not`
+#[derive(Debug, PartialEq, Clone)]
+pub enum Filter {
+    Identity,
+    Field(String),
+    Index(i64),
+    Iterate,
+    Literal(Value),
+    Pipe(Box<Filter>, Box<Filter>),
+    Comma(Box<Filter>, Box<Filter>),
+    Compare(CompareOp, Box<Filter>, Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Collect(Box<Filter>),
+    Object(Vec<(String, Filter)>),
+    Call(String, Option<Box<Filter>>),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CompareOp {
+    Eq,
+    NotEq,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Dot,
+    Ident(String),
+    Str(String),
+    Num(f64),
+    BracketOpen,
+    BracketClose,
+    ParenOpen,
+    ParenClose,
+    CurlyBracketOpen,
+    CurlyBracketClose,
+    Pipe,
+    Comma,
+    Colon,
+    Op(CompareOp),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            ' ' | '\t' | '\n' => (),
+            '.' => tokens.push(Token::Dot),
+            '[' => tokens.push(Token::BracketOpen),
+            ']' => tokens.push(Token::BracketClose),
+            '(' => tokens.push(Token::ParenOpen),
+            ')' => tokens.push(Token::ParenClose),
+            '{' => tokens.push(Token::CurlyBracketOpen),
+            '}' => tokens.push(Token::CurlyBracketClose),
+            '|' => tokens.push(Token::Pipe),
+            ',' => tokens.push(Token::Comma),
+            ':' => tokens.push(Token::Colon),
+            '=' | '!' if next == Some('=') => {
+                tokens.push(Token::Op(if c == '=' { CompareOp::Eq } else { CompareOp::NotEq }));
+                i += 1;
+            }
+            '<' | '>' => {
+                let or_equal = next == Some('=');
+                tokens.push(Token::Op(match (c, or_equal) {
+                    ('<', false) => CompareOp::Less,
+                    ('<', true) => CompareOp::LessEq,
+                    (_, false) => CompareOp::Greater,
+                    (_, true) => CompareOp::GreaterEq,
+                }));
+                if or_equal { i += 1; }
+            }
+            '"' => {
+                let mut literal = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        Some('"') => break,
+                        Some('\\') => {
+                            match chars.get(i + 1) {
+                                Some('n') => literal.push('\n'),
+                                Some('t') => literal.push('\t'),
+                                Some(escaped) => literal.push(*escaped),
+                                None => return Err(String::from("Unterminated string in filter")),
+                            }
+                            i += 2;
+                        }
+                        Some(ch) => {
+                            literal.push(*ch);
+                            i += 1;
+                        }
+                        None => return Err(String::from("Unterminated string in filter")),
+                    }
+                }
+                tokens.push(Token::Str(literal));
+            }
+            c if c.is_ascii_digit() || (c == '-' && next.is_some_and(|n| n.is_ascii_digit())) => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') { i += 1; }
+                let number: String = chars[start..i].iter().collect();
+                tokens.push(Token::Num(number.parse::<f64>().map_err(|_| format!("Invalid number `{}` in filter", number))?));
+                continue;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') { i += 1; }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+                continue;
+            }
+            c => return Err(format!("Unexpected `{}` in filter", c)),
+        }
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> { self.tokens.get(self.position) }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(t) if t == expected => Ok(()),
+            other => Err(format!("Expected {:?} but got {:?}", expected, other)),
+        }
+    }
+
+    fn parse_pipe(&mut self) -> Result<Filter, String> {
+        let mut left = self.parse_comma()?;
+        while self.peek() == Some(&Token::Pipe) {
+            self.next();
+            left = Filter::Pipe(Box::new(left), Box::new(self.parse_comma()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_comma(&mut self) -> Result<Filter, String> {
+        let mut left = self.parse_or()?;
+        while self.peek() == Some(&Token::Comma) {
+            self.next();
+            left = Filter::Comma(Box::new(left), Box::new(self.parse_or()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_or(&mut self) -> Result<Filter, String> {
+        let mut left = self.parse_and()?;
+        while self.peek() == Some(&Token::Ident(String::from("or"))) {
+            self.next();
+            left = Filter::Or(Box::new(left), Box::new(self.parse_and()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Filter, String> {
+        let mut left = self.parse_compare()?;
+        while self.peek() == Some(&Token::Ident(String::from("and"))) {
+            self.next();
+            left = Filter::And(Box::new(left), Box::new(self.parse_compare()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_compare(&mut self) -> Result<Filter, String> {
+        let left = self.parse_postfix()?;
+        if let Some(Token::Op(op)) = self.peek().cloned() {
+            self.next();
+            Ok(Filter::Compare(op, Box::new(left), Box::new(self.parse_postfix()?)))
+        } else {
+            Ok(left)
+        }
+    }
+
+    fn parse_postfix(&mut self) -> Result<Filter, String> {
+        let mut filter = self.parse_primary()?;
+        loop {
+            let suffix = match (self.peek(), self.tokens.get(self.position + 1)) {
+                (Some(Token::Dot), Some(Token::Ident(_))) | (Some(Token::Dot), Some(Token::Str(_))) => {
+                    self.next();
+                    self.parse_field()?
+                }
+                (Some(Token::Dot), Some(Token::BracketOpen)) => {
+                    self.next();
+                    self.parse_bracket()?
+                }
+                (Some(Token::BracketOpen), _) => self.parse_bracket()?,
+                _ => break,
+            };
+            filter = Filter::Pipe(Box::new(filter), Box::new(suffix));
+        }
+        Ok(filter)
+    }
+
+    fn parse_field(&mut self) -> Result<Filter, String> {
+        match self.next() {
+            Some(Token::Ident(name)) | Some(Token::Str(name)) => Ok(Filter::Field(name)),
+            other => Err(format!("Expected field name but got {:?}", other)),
+        }
+    }
+
+    // `[]`, `[0]` or `["key"]`, with the opening bracket not yet consumed
+    fn parse_bracket(&mut self) -> Result<Filter, String> {
+        self.expect(Token::BracketOpen)?;
+        let filter = match self.next() {
+            Some(Token::BracketClose) => return Ok(Filter::Iterate),
+            Some(Token::Num(n)) => Filter::Index(n as i64),
+            Some(Token::Str(key)) => Filter::Field(key),
+            other => return Err(format!("Expected index but got {:?}", other)),
+        };
+        self.expect(Token::BracketClose)?;
+        Ok(filter)
+    }
+
+    fn parse_primary(&mut self) -> Result<Filter, String> {
+        match self.peek().cloned() {
+            Some(Token::Dot) => {
+                self.next();
+                match self.peek() {
+                    Some(Token::Ident(_)) | Some(Token::Str(_)) => self.parse_field(),
+                    Some(Token::BracketOpen) => self.parse_bracket(),
+                    _ => Ok(Filter::Identity),
+                }
+            }
+            Some(Token::Num(n)) => {
+                self.next();
+                Ok(Filter::Literal(serde_json::Number::from_f64(n).map_or(Value::Null, Value::Number)))
+            }
+            Some(Token::Str(s)) => {
+                self.next();
+                Ok(Filter::Literal(Value::String(s)))
+            }
+            Some(Token::ParenOpen) => {
+                self.next();
+                let inner = self.parse_pipe()?;
+                self.expect(Token::ParenClose)?;
+                Ok(inner)
+            }
+            Some(Token::BracketOpen) => {
+                self.next();
+                if self.peek() == Some(&Token::BracketClose) {
+                    self.next();
+                    return Ok(Filter::Literal(Value::Array(vec![])));
+                }
+                let inner = self.parse_pipe()?;
+                self.expect(Token::BracketClose)?;
+                Ok(Filter::Collect(Box::new(inner)))
+            }
+            Some(Token::CurlyBracketOpen) => {
+                self.next();
+                self.parse_object()
+            }
+            Some(Token::Ident(name)) => {
+                self.next();
+                match name.as_str() {
+                    "true" => Ok(Filter::Literal(Value::Bool(true))),
+                    "false" => Ok(Filter::Literal(Value::Bool(false))),
+                    "null" => Ok(Filter::Literal(Value::Null)),
+                    _ => {
+                        let argument = if self.peek() == Some(&Token::ParenOpen) {
+                            self.next();
+                            let argument = self.parse_pipe()?;
+                            self.expect(Token::ParenClose)?;
+                            Some(Box::new(argument))
+                        } else { None };
+                        Ok(Filter::Call(name, argument))
+                    }
+                }
+            }
+            other => Err(format!("Unexpected {:?} in filter", other)),
+        }
+    }
+
+    // `{name, id: .user.id, "full name": .name}`, with the opening bracket already consumed
+    fn parse_object(&mut self) -> Result<Filter, String> {
+        let mut fields = vec![];
+        if self.peek() == Some(&Token::CurlyBracketClose) {
+            self.next();
+            return Ok(Filter::Object(fields));
+        }
+        loop {
+            let key = match self.next() {
+                Some(Token::Ident(k)) | Some(Token::Str(k)) => k,
+                other => return Err(format!("Expected object key but got {:?}", other)),
+            };
+            let value = if self.peek() == Some(&Token::Colon) {
+                self.next();
+                self.parse_or()?
+            } else {
+                Filter::Field(key.clone())
+            };
+            fields.push((key, value));
+            match self.next() {
+                Some(Token::Comma) => continue,
+                Some(Token::CurlyBracketClose) => break,
+                other => return Err(format!("Expected `,` or `}}` but got {:?}", other)),
+            }
+        }
+        Ok(Filter::Object(fields))
+    }
+}
+
+impl Filter {
+    pub fn from(s: &str) -> Result<Filter, String> {
+        let mut parser = Parser { tokens: tokenize(s)?, position: 0 };
+        if parser.tokens.is_empty() {
+            return Ok(Filter::Identity);
+        }
+        let filter = parser.parse_pipe()?;
+        match parser.peek() {
+            None => Ok(filter),
+            Some(t) => Err(format!("Unexpected {:?} in filter `{}`", t, s)),
+        }
+    }
+
+    pub fn apply(&self, value: &Value) -> Result<Vec<Value>, String> {
+        match self {
+            Filter::Identity => Ok(vec![value.clone()]),
+            Filter::Field(name) => match value {
+                Value::Object(map) => Ok(vec![map.get(name).cloned().unwrap_or(Value::Null)]),
+                Value::Null => Ok(vec![Value::Null]),
+                other => Err(format!("Cannot index {} with \"{}\"", type_name(other), name)),
+            },
+            Filter::Index(i) => match value {
+                Value::Array(list) => {
+                    let index = if *i < 0 { list.len() as i64 + i } else { *i };
+                    Ok(vec![usize::try_from(index).ok().and_then(|idx| list.get(idx)).cloned().unwrap_or(Value::Null)])
+                }
+                Value::Null => Ok(vec![Value::Null]),
+                other => Err(format!("Cannot index {} with number", type_name(other))),
+            },
+            Filter::Iterate => match value {
+                Value::Array(list) => Ok(list.clone()),
+                Value::Object(map) => Ok(map.values().cloned().collect()),
+                other => Err(format!("Cannot iterate over {}", type_name(other))),
+            },
+            Filter::Literal(literal) => Ok(vec![literal.clone()]),
+            Filter::Pipe(left, right) => {
+                let mut results = vec![];
+                for v in left.apply(value)? {
+                    results.extend(right.apply(&v)?);
+                }
+                Ok(results)
+            }
+            Filter::Comma(left, right) => Ok([left.apply(value)?, right.apply(value)?].concat()),
+            Filter::Compare(op, left, right) => {
+                let mut results = vec![];
+                for l in left.apply(value)? {
+                    for r in right.apply(value)? {
+                        results.push(Value::Bool(compare(*op, &l, &r)));
+                    }
+                }
+                Ok(results)
+            }
+            Filter::And(left, right) => Ok(vec![Value::Bool(
+                left.apply(value)?.iter().any(is_truthy) && right.apply(value)?.iter().any(is_truthy)
+            )]),
+            Filter::Or(left, right) => Ok(vec![Value::Bool(
+                left.apply(value)?.iter().any(is_truthy) || right.apply(value)?.iter().any(is_truthy)
+            )]),
+            Filter::Collect(inner) => Ok(vec![Value::Array(inner.apply(value)?)]),
+            Filter::Object(fields) => {
+                let mut objects = vec![Map::new()];
+                for (key, field_filter) in fields {
+                    let values = field_filter.apply(value)?;
+                    objects = objects.iter()
+                        .flat_map(|o| values.iter().map(move |v| {
+                            let mut extended = o.clone();
+                            extended.insert(key.clone(), v.clone());
+                            extended
+                        }))
+                        .collect();
+                }
+                Ok(objects.into_iter().map(Value::Object).collect())
+            }
+            Filter::Call(name, argument) => call(name, argument.as_deref(), value),
+        }
+    }
+
+    pub fn apply_all(&self, values: &[Value]) -> Result<Vec<Value>, String> {
+        let mut results = vec![];
+        for v in values {
+            results.extend(self.apply(v)?);
+        }
+        Ok(results)
+    }
+}
+
+fn call(name: &str, argument: Option<&Filter>, value: &Value) -> Result<Vec<Value>, String> {
+    match (name, argument) {
+        ("select", Some(condition)) => Ok(condition.apply(value)?.iter()
+            .filter(|c| is_truthy(c))
+            .map(|_| value.clone())
+            .collect()),
+        ("map", Some(mapping)) => Filter::Collect(Box::new(Filter::Pipe(Box::new(Filter::Iterate), Box::new(mapping.clone())))).apply(value),
+        ("not", None) => Ok(vec![Value::Bool(!is_truthy(value))]),
+        ("length", None) => match value {
+            Value::Array(list) => Ok(vec![Value::from(list.len())]),
+            Value::Object(map) => Ok(vec![Value::from(map.len())]),
+            Value::String(s) => Ok(vec![Value::from(s.chars().count())]),
+            Value::Null => Ok(vec![Value::from(0)]),
+            Value::Number(n) => Ok(vec![serde_json::Number::from_f64(n.as_f64().unwrap_or(0.0).abs()).map_or(Value::Null, Value::Number)]),
+            other => Err(format!("{} has no length", type_name(other))),
+        },
+        ("keys", None) => match value {
+            Value::Object(map) => Ok(vec![Value::Array(map.keys().map(|k| Value::String(k.clone())).collect())]),
+            Value::Array(list) => Ok(vec![Value::Array((0..list.len()).map(Value::from).collect())]),
+            other => Err(format!("{} has no keys", type_name(other))),
+        },
+        (name, _) => Err(format!("Unknown function `{}`", name)),
+    }
+}
+
+fn type_name(value: &Value) -> &str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn is_truthy(value: &Value) -> bool {
+    !matches!(value, Value::Null | Value::Bool(false))
+}
+
+fn compare(op: CompareOp, left: &Value, right: &Value) -> bool {
+    let ordering = match (left, right) {
+        (Value::Number(l), Value::Number(r)) => l.as_f64().partial_cmp(&r.as_f64()),
+        (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+        (Value::Bool(l), Value::Bool(r)) => Some(l.cmp(r)),
+        (l, r) if l == r => Some(Ordering::Equal),
+        _ => None,
+    };
+    match op {
+        CompareOp::Eq => left == right || ordering == Some(Ordering::Equal),
+        CompareOp::NotEq => !(left == right || ordering == Some(Ordering::Equal)),
+        CompareOp::Less => ordering == Some(Ordering::Less),
+        CompareOp::LessEq => matches!(ordering, Some(Ordering::Less) | Some(Ordering::Equal)),
+        CompareOp::Greater => ordering == Some(Ordering::Greater),
+        CompareOp::GreaterEq => matches!(ordering, Some(Ordering::Greater) | Some(Ordering::Equal)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn run(filter: &str, value: Value) -> Vec<Value> {
+        Filter::from(filter).unwrap().apply(&value).unwrap()
+    }
+
+    #[test]
+    fn paths() {
+        let value = json!({"user": {"name": "john", "tags": ["a", "b"]}});
+        assert_eq!(run(".", value.clone()), vec![value.clone()]);
+        assert_eq!(run(".user.name", value.clone()), vec![json!("john")]);
+        assert_eq!(run(".user.tags[1]", value.clone()), vec![json!("b")]);
+        assert_eq!(run(".user.tags[-1]", value.clone()), vec![json!("b")]);
+        assert_eq!(run(".user.tags[]", value.clone()), vec![json!("a"), json!("b")]);
+        assert_eq!(run(".[\"user\"].missing", value), vec![Value::Null]);
+    }
+
+    #[test]
+    fn select_and_construct() {
+        let value = json!({"items": [{"id": 1, "price": 10}, {"id": 2, "price": 30}, {"id": 3, "price": 50}]});
+        assert_eq!(run(".items[] | select(.price > 20) | .id", value.clone()), vec![json!(2), json!(3)]);
+        assert_eq!(run("[.items[] | select(.id == 1 or .id == 3) | {id, cost: .price}]", value.clone()),
+                   vec![json!([{"id": 1, "cost": 10}, {"id": 3, "cost": 50}])]);
+        assert_eq!(run(".items | map(.id) | length", value.clone()), vec![json!(3)]);
+        assert_eq!(run(".items[0] | keys", value), vec![json!(["id", "price"])]);
+    }
+
+    #[test]
+    fn invalid_filters() {
+        assert!(Filter::from(".a |").is_err());
+        assert!(Filter::from(".a[").is_err());
+        assert!(Filter::from("\"open").is_err());
+        assert!(Filter::from(".a").unwrap().apply(&json!([1])).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use std::fmt::{Display, Formatter};
+use colored::Colorize;
+use serde::Deserialize;
+use serde_json::Value;
+use crate::descriptors;
+use crate::grpc_request_dsl::ServiceRequest;
+
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize)]
+#[serde(try_from = "String")]
+pub enum OutputFormat {
+    Pretty,
+    Compact,
+    Yaml,
+    Table,
+    Raw,
+    Hex,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 6] = [
+        OutputFormat::Pretty,
+        OutputFormat::Compact,
+        OutputFormat::Yaml,
+        OutputFormat::Table,
+        OutputFormat::Raw,
+        OutputFormat::Hex,
+    ];
+
+    pub fn from(s: &str) -> Result<OutputFormat, String> {
+        match s.to_lowercase().as_str() {
+            "pretty" | "json" => Ok(OutputFormat::Pretty),
+            "compact" => Ok(OutputFormat::Compact),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "table" => Ok(OutputFormat::Table),
+            "raw" => Ok(OutputFormat::Raw),
+            "hex" => Ok(OutputFormat::Hex),
+            _ => Err(format!("Invalid output format: {:?}. Expected one of pretty, compact, yaml, table, raw, hex", s)),
+        }
+    }
+
+    pub fn description(&self) -> &str {
+        match self {
+            OutputFormat::Pretty  => "Pretty JSON with colors",
+            OutputFormat::Compact => "Compact JSON, one line per message",
+            OutputFormat::Yaml    => "YAML",
+            OutputFormat::Table   => "Table of repeated messages",
+            OutputFormat::Raw     => "Raw protobuf binary",
+            OutputFormat::Hex     => "Protobuf binary as hex",
+        }
+    }
+}
+
+impl TryFrom<String> for OutputFormat {
+    type Error = String;
+    fn try_from(s: String) -> Result<OutputFormat, String> {
+        OutputFormat::from(&s)
+    }
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            OutputFormat::Pretty  => "pretty",
+            OutputFormat::Compact => "compact",
+            OutputFormat::Yaml    => "yaml",
+            OutputFormat::Table   => "table",
+            OutputFormat::Raw     => "raw",
+            OutputFormat::Hex     => "hex",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+pub fn render(format: OutputFormat, messages: &[Value], request: &ServiceRequest) -> Result<Vec<u8>, String> {
+    match format {
+        OutputFormat::Pretty => Ok(messages.iter().map(colored_json).collect::<Vec<String>>().join("\n").into_bytes()),
+        OutputFormat::Compact => Ok(messages.iter().map(|m| m.to_string()).collect::<Vec<String>>().join("\n").into_bytes()),
+        OutputFormat::Yaml => to_yaml(messages).map(String::into_bytes),
+        OutputFormat::Table => Ok(to_table(messages, colored::control::SHOULD_COLORIZE.should_colorize()).into_bytes()),
+        OutputFormat::Raw | OutputFormat::Hex => {
+            let method = descriptors::method_of(request)?;
+            let encoded = messages.iter()
+                .map(|m| descriptors::encode_json(&method.output(), m))
+                .collect::<Result<Vec<Vec<u8>>, String>>()?;
+            if format == OutputFormat::Raw {
+                // length delimited so that a stream of messages can be split again
+                Ok(encoded.iter().flat_map(|bytes| {
+                    let mut delimited = vec![];
+                    prost::encoding::encode_varint(bytes.len() as u64, &mut delimited);
+                    delimited.extend_from_slice(bytes);
+                    delimited
+                }).collect())
+            } else {
+                Ok(encoded.iter().map(|bytes| to_hex(bytes)).collect::<Vec<String>>().join("\n").into_bytes())
+            }
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<String>>().join(" ")
+}
+
+fn to_yaml(messages: &[Value]) -> Result<String, String> {
+    messages.iter()
+        .map(|m| serde_yaml::to_string(m).map_err(|e| format!("Failed to render YAML: {}", e)))
+        .collect::<Result<Vec<String>, String>>()
+        .map(|documents| documents.join("---\n"))
+}
+
+pub fn colored_json(value: &Value) -> String {
+    fn write_value(value: &Value, indent: usize, out: &mut String) {
+        let padding = "  ".repeat(indent + 1);
+        let closing_padding = "  ".repeat(indent);
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                out.push_str("{\n");
+                let fields: Vec<String> = map.iter().map(|(k, v)| {
+                    let mut field = format!("{}{}: ", padding, Value::String(k.clone()).to_string().blue());
+                    write_value(v, indent + 1, &mut field);
+                    field
+                }).collect();
+                out.push_str(&fields.join(",\n"));
+                out.push_str(&format!("\n{}}}", closing_padding));
+            }
+            Value::Array(list) if !list.is_empty() => {
+                out.push_str("[\n");
+                let elements: Vec<String> = list.iter().map(|v| {
+                    let mut element = padding.clone();
+                    write_value(v, indent + 1, &mut element);
+                    element
+                }).collect();
+                out.push_str(&elements.join(",\n"));
+                out.push_str(&format!("\n{}]", closing_padding));
+            }
+            Value::String(_) => out.push_str(&value.to_string().green().to_string()),
+            Value::Number(_) => out.push_str(&value.to_string().yellow().to_string()),
+            Value::Bool(_) | Value::Null => out.push_str(&value.to_string().magenta().to_string()),
+            _ => out.push_str(&value.to_string()),
+        }
+    }
+    let mut out = String::new();
+    write_value(value, 0, &mut out);
+    out
+}
+
+// A stream of messages becomes rows. A single message becomes rows of its first repeated message field.
+fn table_rows(messages: &[Value]) -> Vec<Value> {
+    if let [single] = messages {
+        if let Some(Value::Array(list)) = single.as_object()
+            .and_then(|m| m.values().find(|v| v.as_array().is_some_and(|l| l.iter().all(Value::is_object)))) {
+            return list.clone();
+        }
+    }
+    messages.to_vec()
+}
+
+fn to_table(messages: &[Value], color: bool) -> String {
+    let rows = table_rows(messages);
+    let mut columns: Vec<String> = vec![];
+    for row in &rows {
+        if let Value::Object(map) = row {
+            for key in map.keys() {
+                if !columns.contains(key) { columns.push(key.clone()) }
+            }
+        }
+    }
+    if columns.is_empty() {
+        return rows.iter().map(|r| r.to_string()).collect::<Vec<String>>().join("\n");
+    }
+
+    let cells: Vec<Vec<String>> = rows.iter().map(|row| {
+        columns.iter().map(|c| match row.get(c) {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Null) | None => String::new(),
+            Some(v) => v.to_string(),
+        }).collect()
+    }).collect();
+    let widths: Vec<usize> = columns.iter().enumerate()
+        .map(|(i, c)| cells.iter().map(|r| r[i].chars().count()).chain(std::iter::once(c.chars().count())).max().unwrap_or(0))
+        .collect();
+    let format_row = |row: &[String]| -> String {
+        row.iter().zip(widths.iter())
+            .map(|(cell, width)| format!("{:<width$}", cell, width = *width))
+            .collect::<Vec<String>>()
+            .join(" | ")
+    };
+
+    let header = format_row(&columns);
+    let mut lines = vec![if color { header.bold().to_string() } else { header }];
+    lines.push(widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<String>>().join("-+-"));
+    cells.iter().for_each(|row| lines.push(format_row(row)));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn compact_prints_one_line_per_message() {
+        let messages = vec![json!({"name": "john"}), json!({"name": "jane"})];
+        let rendered = render(OutputFormat::Compact, &messages, &ServiceRequest::default()).unwrap();
+        assert_eq!(String::from_utf8(rendered).unwrap(), "{\"name\":\"john\"}\n{\"name\":\"jane\"}");
+    }
+
+    #[test]
+    fn table_uses_repeated_field_of_single_message() {
+        let messages = vec![json!({"users": [{"id": 1, "name": "john"}, {"id": 22, "nick": "j"}]})];
+        let expected = "id | name | nick\n---+------+-----\n1  | john |     \n22 |      | j   ";
+        assert_eq!(to_table(&messages, false), expected);
+    }
+}
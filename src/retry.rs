@@ -0,0 +1,139 @@
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+use rand::Rng;
+use serde::{de, Deserialize, Deserializer};
+use crate::grpc_status::{ErrorDetail, GrpcStatus, StatusCode};
+use crate::latency::format_duration;
+use crate::util::{deserialize_duration, deserialize_parsed};
+
+// `[retry]` of settings
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetryPolicy {
+    #[serde(deserialize_with = "deserialize_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(rename = "codes", deserialize_with = "deserialize_codes")]
+    pub retryable: Vec<StatusCode>,
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub initial_backoff: Duration,
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub max_backoff: Duration,
+    pub multiplier: f64,
+}
+
+impl RetryPolicy {
+    // a single attempt, i.e. no retry
+    pub fn none() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            retryable: vec![StatusCode::Unavailable],
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2.0,
+        }
+    }
+
+    // `UNAVAILABLE,RESOURCE_EXHAUSTED` or `14,8`
+    pub fn parse_codes(s: &str) -> Result<Vec<StatusCode>, String> {
+        s.split(',')
+            .map(|c| c.trim())
+            .filter(|c| !c.is_empty())
+            .map(StatusCode::from_name)
+            .collect()
+    }
+
+    pub fn should_retry(&self, status: &GrpcStatus, attempt: u32) -> bool {
+        attempt < self.max_attempts && self.retryable.contains(&status.code)
+    }
+
+    // exponential backoff with full jitter. The delay asked by the server(`RetryInfo`) takes precedence,
+    // up to `max_backoff` and the deadline so a misbehaving server can't park the client
+    pub fn backoff(&self, attempt: u32, status: &GrpcStatus, deadline: Option<Duration>) -> Duration {
+        let retry_info = status.details.iter().find_map(|d| match d {
+            ErrorDetail::RetryInfo { retry_delay } => Some(*retry_delay),
+            _ => None,
+        });
+        let delay = retry_info.unwrap_or_else(|| {
+            let ceiling = self.ceiling(attempt);
+            ceiling.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+        });
+        delay.min(self.max_backoff).min(deadline.unwrap_or(Duration::MAX))
+    }
+
+    fn ceiling(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(32) as i32;
+        let backoff = self.initial_backoff.as_secs_f64() * self.multiplier.max(1.0).powi(exponent);
+        Duration::from_secs_f64(backoff.min(self.max_backoff.as_secs_f64()))
+    }
+}
+
+impl Display for RetryPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.max_attempts <= 1 {
+            return write!(f, "no retry");
+        }
+        let codes: Vec<&str> = self.retryable.iter().map(|c| c.name()).collect();
+        write!(f, "up to {} attempts on {}, backoff {}..{}",
+               self.max_attempts, codes.join(","), format_duration(self.initial_backoff), format_duration(self.max_backoff))
+    }
+}
+
+// the first attempt counts, so less than one makes no call at all
+fn deserialize_max_attempts<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    match u32::deserialize(deserializer)? {
+        0 => Err(de::Error::custom("`max_attempts` must be at least 1")),
+        max_attempts => Ok(max_attempts),
+    }
+}
+
+fn deserialize_codes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<StatusCode>, D::Error> {
+    deserialize_parsed(deserializer, RetryPolicy::parse_codes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(code: StatusCode, details: Vec<ErrorDetail>) -> GrpcStatus {
+        GrpcStatus { code, message: String::new(), details }
+    }
+
+    #[test]
+    fn retries_only_retryable_codes_within_attempts() {
+        let policy = RetryPolicy { max_attempts: 3, ..RetryPolicy::none() };
+        assert!(policy.should_retry(&status(StatusCode::Unavailable, vec![]), 1));
+        assert!(policy.should_retry(&status(StatusCode::Unavailable, vec![]), 2));
+        assert!(!policy.should_retry(&status(StatusCode::Unavailable, vec![]), 3));
+        assert!(!policy.should_retry(&status(StatusCode::InvalidArgument, vec![]), 1));
+        assert!(!RetryPolicy::none().should_retry(&status(StatusCode::Unavailable, vec![]), 1));
+    }
+
+    #[test]
+    fn backoff_grows_up_to_max() {
+        let policy = RetryPolicy { max_attempts: 10, ..RetryPolicy::none() };
+        assert_eq!(policy.ceiling(1), Duration::from_millis(100));
+        assert_eq!(policy.ceiling(3), Duration::from_millis(400));
+        assert_eq!(policy.ceiling(10), Duration::from_secs(5));
+        let unavailable = status(StatusCode::Unavailable, vec![]);
+        assert!((1..10).all(|attempt| policy.backoff(attempt, &unavailable, None) <= policy.ceiling(attempt)));
+
+        let with_retry_info = status(StatusCode::Unavailable, vec![ErrorDetail::RetryInfo { retry_delay: Duration::from_secs(3) }]);
+        assert_eq!(policy.backoff(1, &with_retry_info, None), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn server_delay_is_capped() {
+        let policy = RetryPolicy { max_attempts: 3, ..RetryPolicy::none() };
+        let hours = status(StatusCode::Unavailable, vec![ErrorDetail::RetryInfo { retry_delay: Duration::from_secs(3 * 3600) }]);
+        assert_eq!(policy.backoff(1, &hours, None), Duration::from_secs(5));
+        assert_eq!(policy.backoff(1, &hours, Some(Duration::from_secs(2))), Duration::from_secs(2));
+        assert!(policy.backoff(1, &status(StatusCode::Unavailable, vec![]), Some(Duration::from_millis(10))) <= Duration::from_millis(10));
+    }
+
+    #[test]
+    fn parse_status_codes() {
+        assert_eq!(RetryPolicy::parse_codes("UNAVAILABLE, resource_exhausted,4").unwrap(),
+                   vec![StatusCode::Unavailable, StatusCode::ResourceExhausted, StatusCode::DeadlineExceeded]);
+        assert!(RetryPolicy::parse_codes("NOT_A_CODE").is_err());
+    }
+}
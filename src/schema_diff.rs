@@ -0,0 +1,298 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use prost_reflect::{Cardinality, DescriptorPool, EnumDescriptor, FieldDescriptor, Kind, MessageDescriptor, ServiceDescriptor};
+use crate::descriptors;
+use crate::grpc_request_dsl::{Network, Timeouts};
+use crate::text_coloring::{to_error, to_success};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Severity {
+    Breaking,
+    Safe,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Change {
+    pub severity: Severity,
+    pub description: String,
+}
+
+impl Change {
+    fn breaking(description: String) -> Change {
+        Change { severity: Severity::Breaking, description }
+    }
+
+    fn safe(description: String) -> Change {
+        Change { severity: Severity::Safe, description }
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.severity {
+            Severity::Breaking => write!(f, "{} {}", to_error("BREAKING"), self.description),
+            Severity::Safe => write!(f, "{}     {}", to_success("SAFE"), self.description),
+        }
+    }
+}
+
+// `old` and `new` are either targets or protoset files
+pub fn run(old: &str, new: &str, timeouts: Timeouts, network: &Network) -> Result<bool, String> {
+    let old_pool = descriptors::pool_from_source(old, &timeouts, network)?;
+    let new_pool = descriptors::pool_from_source(new, &timeouts, network)?;
+    let changes = diff(&old_pool, &new_pool);
+    changes.iter().for_each(|c| println!("{}", c));
+    let breaking = changes.iter().filter(|c| c.severity == Severity::Breaking).count();
+    println!("\n{} breaking, {} safe change(s)", breaking, changes.len() - breaking);
+    Ok(breaking == 0)
+}
+
+pub fn diff(old: &DescriptorPool, new: &DescriptorPool) -> Vec<Change> {
+    let mut changes = vec![];
+    compare(by_name(old.services(), |s| s.full_name().to_string()), by_name(new.services(), |s| s.full_name().to_string()), "service", &mut changes, diff_service);
+    compare(by_name(old.all_messages().filter(|m| !m.is_map_entry()), |m| m.full_name().to_string()),
+            by_name(new.all_messages().filter(|m| !m.is_map_entry()), |m| m.full_name().to_string()), "message", &mut changes, diff_message);
+    compare(by_name(old.all_enums(), |e| e.full_name().to_string()), by_name(new.all_enums(), |e| e.full_name().to_string()), "enum", &mut changes, diff_enum);
+    changes
+}
+
+fn by_name<T>(items: impl Iterator<Item = T>, name: impl Fn(&T) -> String) -> BTreeMap<String, T> {
+    items.map(|item| (name(&item), item)).collect()
+}
+
+// removing is breaking, adding is safe and the rest is up to `f`
+fn compare<K: Ord + Display, T>(old: BTreeMap<K, T>, new: BTreeMap<K, T>, kind: &str, changes: &mut Vec<Change>, f: impl Fn(&K, &T, &T, &mut Vec<Change>)) {
+    for (key, old_item) in &old {
+        match new.get(key) {
+            Some(new_item) => f(key, old_item, new_item, changes),
+            None => changes.push(Change::breaking(format!("{} `{}` removed", kind, key))),
+        }
+    }
+    new.keys().filter(|key| !old.contains_key(key))
+        .for_each(|key| changes.push(Change::safe(format!("{} `{}` added", kind, key))));
+}
+
+fn diff_service(name: &String, old: &ServiceDescriptor, new: &ServiceDescriptor, changes: &mut Vec<Change>) {
+    let old_methods = by_name(old.methods(), |m| format!("{}/{}", name, m.name()));
+    let new_methods = by_name(new.methods(), |m| format!("{}/{}", name, m.name()));
+    compare(old_methods, new_methods, "method", changes, |name, old, new, changes| {
+        if old.input().full_name() != new.input().full_name() {
+            changes.push(Change::breaking(format!("method `{}` request `{}` -> `{}`", name, old.input().full_name(), new.input().full_name())));
+        }
+        if old.output().full_name() != new.output().full_name() {
+            changes.push(Change::breaking(format!("method `{}` response `{}` -> `{}`", name, old.output().full_name(), new.output().full_name())));
+        }
+        if (old.is_client_streaming(), old.is_server_streaming()) != (new.is_client_streaming(), new.is_server_streaming()) {
+            changes.push(Change::breaking(format!("method `{}` streaming {} -> {}", name, streaming_of(old), streaming_of(new))));
+        }
+    });
+}
+
+fn streaming_of(method: &prost_reflect::MethodDescriptor) -> &str {
+    match (method.is_client_streaming(), method.is_server_streaming()) {
+        (false, false) => "unary",
+        (true, false) => "client streaming",
+        (false, true) => "server streaming",
+        (true, true) => "bidirectional streaming",
+    }
+}
+
+fn diff_message(name: &String, old: &MessageDescriptor, new: &MessageDescriptor, changes: &mut Vec<Change>) {
+    let old_fields: BTreeMap<u32, FieldDescriptor> = old.fields().map(|f| (f.number(), f)).collect();
+    let new_fields: BTreeMap<u32, FieldDescriptor> = new.fields().map(|f| (f.number(), f)).collect();
+    for (number, old_field) in &old_fields {
+        let field = format!("`{}.{}`({})", name, old_field.name(), number);
+        let new_field = match new_fields.get(number) {
+            Some(f) => f,
+            None => {
+                changes.push(Change::breaking(format!("field {} removed", field)));
+                continue;
+            }
+        };
+        // JSON uses field names
+        if old_field.name() != new_field.name() {
+            changes.push(Change::breaking(format!("field {} renamed to `{}`, breaks JSON", field, new_field.name())));
+        }
+        let (old_kind, new_kind) = (kind_name(old_field), kind_name(new_field));
+        if old_kind != new_kind {
+            let note = if wire_compatible(&old_field.kind(), &new_field.kind()) { ", wire compatible but breaks JSON" } else { "" };
+            changes.push(Change::breaking(format!("field {} type {} -> {}{}", field, old_kind, new_kind, note)));
+        }
+        let (old_label, new_label) = (label_of(old_field), label_of(new_field));
+        if old_label != new_label {
+            // only presence tracking differs between `optional` and a singular field. `required` fails messages without it
+            let change = format!("field {} label {} -> {}", field, old_label, new_label);
+            let required = old_field.cardinality() == Cardinality::Required || new_field.cardinality() == Cardinality::Required;
+            if old_field.is_list() == new_field.is_list() && !old_field.is_map() && !new_field.is_map() && !required {
+                changes.push(Change::safe(change));
+            } else {
+                changes.push(Change::breaking(change));
+            }
+        }
+        let (old_oneof, new_oneof) = (oneof_of(old_field), oneof_of(new_field));
+        if old_oneof != new_oneof {
+            changes.push(Change::breaking(format!("field {} oneof {} -> {}",
+                field, old_oneof.unwrap_or_else(|| String::from("none")), new_oneof.unwrap_or_else(|| String::from("none")))));
+        }
+    }
+    for (number, f) in new_fields.iter().filter(|(number, _)| !old_fields.contains_key(number)) {
+        let added = format!("field `{}.{}`({}) added", name, f.name(), number);
+        match f.cardinality() {
+            Cardinality::Required => changes.push(Change::breaking(format!("{} as required", added))),
+            _ => changes.push(Change::safe(added)),
+        }
+    }
+}
+
+fn diff_enum(name: &String, old: &EnumDescriptor, new: &EnumDescriptor, changes: &mut Vec<Change>) {
+    let old_values: BTreeMap<i32, String> = old.values().map(|v| (v.number(), v.name().to_string())).collect();
+    let new_values: BTreeMap<i32, String> = new.values().map(|v| (v.number(), v.name().to_string())).collect();
+    for (number, old_value) in &old_values {
+        match new_values.get(number) {
+            None => changes.push(Change::breaking(format!("enum value `{}.{}`({}) removed", name, old_value, number))),
+            Some(new_value) if new_value != old_value =>
+                changes.push(Change::breaking(format!("enum value `{}.{}`({}) renamed to `{}`, breaks JSON", name, old_value, number, new_value))),
+            _ => {}
+        }
+    }
+    new_values.iter().filter(|(number, _)| !old_values.contains_key(number))
+        .for_each(|(number, value)| changes.push(Change::safe(format!("enum value `{}.{}`({}) added", name, value, number))));
+}
+
+pub fn kind_name(field: &FieldDescriptor) -> String {
+    match field.kind() {
+        Kind::Message(m) => m.full_name().to_string(),
+        Kind::Enum(e) => e.full_name().to_string(),
+        kind => format!("{:?}", kind).to_lowercase(),
+    }
+}
+
+fn label_of(field: &FieldDescriptor) -> &str {
+    if field.is_map() {
+        "map"
+    } else if field.is_list() {
+        "repeated"
+    } else if field.cardinality() == Cardinality::Required {
+        "required"
+    } else if field.supports_presence() && field.kind().as_message().is_none() {
+        "optional"
+    } else {
+        "singular"
+    }
+}
+
+fn oneof_of(field: &FieldDescriptor) -> Option<String> {
+    field.containing_oneof().filter(|o| !o.is_synthetic()).map(|o| o.name().to_string())
+}
+
+// scalar types sharing an encoding on the wire
+fn wire_compatible(old: &Kind, new: &Kind) -> bool {
+    let group = |kind: &Kind| match kind {
+        Kind::Int32 | Kind::Int64 | Kind::Uint32 | Kind::Uint64 | Kind::Bool | Kind::Enum(_) => 1,
+        Kind::Sint32 | Kind::Sint64 => 2,
+        Kind::Fixed32 | Kind::Sfixed32 => 3,
+        Kind::Fixed64 | Kind::Sfixed64 => 4,
+        Kind::String | Kind::Bytes => 5,
+        _ => 0,
+    };
+    group(old) != 0 && group(old) == group(new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_types::field_descriptor_proto::{Label, Type};
+    use prost_types::{DescriptorProto, EnumDescriptorProto, EnumValueDescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet};
+
+    fn field(name: &str, number: i32, r#type: Type, label: Label) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(String::from(name)),
+            json_name: Some(String::from(name)),
+            number: Some(number),
+            r#type: Some(r#type as i32),
+            label: Some(label as i32),
+            ..Default::default()
+        }
+    }
+
+    fn pool(fields: Vec<FieldDescriptorProto>, values: Vec<(&str, i32)>) -> DescriptorPool {
+        pool_of("proto3", fields, values)
+    }
+
+    fn pool_of(syntax: &str, fields: Vec<FieldDescriptorProto>, values: Vec<(&str, i32)>) -> DescriptorPool {
+        let file = FileDescriptorProto {
+            name: Some(String::from("test.proto")),
+            package: Some(String::from("test")),
+            syntax: Some(String::from(syntax)),
+            message_type: vec![DescriptorProto { name: Some(String::from("Item")), field: fields, ..Default::default() }],
+            enum_type: vec![EnumDescriptorProto {
+                name: Some(String::from("Color")),
+                value: values.into_iter().map(|(name, number)| EnumValueDescriptorProto { name: Some(String::from(name)), number: Some(number), options: None }).collect(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        DescriptorPool::from_file_descriptor_set(FileDescriptorSet { file: vec![file] }).unwrap()
+    }
+
+    fn descriptions(changes: &[Change], severity: Severity) -> Vec<&str> {
+        changes.iter().filter(|c| c.severity == severity).map(|c| c.description.as_str()).collect()
+    }
+
+    #[test]
+    fn identical_schemas_have_no_change() {
+        let schema = pool(vec![field("id", 1, Type::Int64, Label::Optional)], vec![("RED", 0)]);
+        assert!(diff(&schema, &schema).is_empty());
+    }
+
+    #[test]
+    fn classifies_changes() {
+        let old = pool(vec![
+            field("id", 1, Type::Int32, Label::Optional),
+            field("name", 2, Type::String, Label::Optional),
+            field("tags", 3, Type::String, Label::Repeated),
+            field("note", 4, Type::String, Label::Optional),
+        ], vec![("RED", 0), ("BLUE", 1)]);
+        let new = pool(vec![
+            field("id", 1, Type::Int64, Label::Optional),
+            field("title", 2, Type::String, Label::Optional),
+            field("tags", 3, Type::String, Label::Optional),
+            field("price", 5, Type::Double, Label::Optional),
+        ], vec![("RED", 0), ("GREEN", 2)]);
+        let changes = diff(&old, &new);
+
+        assert_eq!(descriptions(&changes, Severity::Breaking), vec![
+            "field `test.Item.id`(1) type int32 -> int64, wire compatible but breaks JSON",
+            "field `test.Item.name`(2) renamed to `title`, breaks JSON",
+            "field `test.Item.tags`(3) label repeated -> singular",
+            "field `test.Item.note`(4) removed",
+            "enum value `test.Color.BLUE`(1) removed",
+        ]);
+        assert_eq!(descriptions(&changes, Severity::Safe), vec![
+            "field `test.Item.price`(5) added",
+            "enum value `test.Color.GREEN`(2) added",
+        ]);
+    }
+
+    #[test]
+    fn required_fields_break() {
+        let old = pool_of("proto2", vec![
+            field("id", 1, Type::Int64, Label::Optional),
+            field("name", 2, Type::String, Label::Required),
+        ], vec![("RED", 0)]);
+        let new = pool_of("proto2", vec![
+            field("id", 1, Type::Int64, Label::Required),
+            field("name", 2, Type::String, Label::Optional),
+            field("code", 3, Type::String, Label::Required),
+            field("note", 4, Type::String, Label::Optional),
+        ], vec![("RED", 0)]);
+        let changes = diff(&old, &new);
+
+        assert_eq!(descriptions(&changes, Severity::Breaking), vec![
+            "field `test.Item.id`(1) label optional -> required",
+            "field `test.Item.name`(2) label required -> optional",
+            "field `test.Item.code`(3) added as required",
+        ]);
+        assert_eq!(descriptions(&changes, Severity::Safe), vec!["field `test.Item.note`(4) added"]);
+    }
+}
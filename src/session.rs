@@ -0,0 +1,67 @@
+use std::io::Write;
+use std::time::Duration;
+use serde_json::Value;
+use crate::grpc_request_dsl::ServiceRequest;
+use crate::latency::LatencyStats;
+use crate::recent_targets::RecentTargets;
+use crate::response_filter::Filter;
+use crate::response_format::{self, OutputFormat};
+
+#[derive(Debug)]
+pub struct Session {
+    pub output_format: OutputFormat,
+    pub last_response: Vec<Value>,
+    pub filter: Option<Filter>,
+    pub latency_stats: LatencyStats,
+    pub recent_targets: RecentTargets,
+    latency_request: Option<ServiceRequest>,
+}
+
+impl Session {
+    pub fn new(output_format: OutputFormat) -> Session {
+        Session { output_format, last_response: vec![], filter: None, latency_stats: LatencyStats::new(), recent_targets: RecentTargets::load(), latency_request: None }
+    }
+
+    // statistics are kept while the same request is repeated
+    pub fn record_latency(&mut self, service_request: &ServiceRequest, latency: Duration) {
+        if self.latency_request.as_ref() != Some(service_request) {
+            self.latency_stats.clear();
+            self.latency_request = Some(service_request.clone());
+        }
+        self.latency_stats.push(latency);
+    }
+
+    pub fn set_output_format(&mut self, output_format: OutputFormat) -> Result<(), String> {
+        check_filtered_format(output_format, &self.filter)?;
+        self.output_format = output_format;
+        Ok(())
+    }
+
+    pub fn set_filter(&mut self, filter: Option<Filter>) -> Result<(), String> {
+        check_filtered_format(self.output_format, &filter)?;
+        self.filter = filter;
+        Ok(())
+    }
+
+    pub fn print_response(&self, service_request: &ServiceRequest) -> Result<(), String> {
+        let messages = match &self.filter {
+            Some(filter) => filter.apply_all(&self.last_response)?,
+            None => self.last_response.clone(),
+        };
+        check_filtered_format(self.output_format, &self.filter)?;
+        let rendered = response_format::render(self.output_format, &messages, service_request)?;
+        let mut stdout = std::io::stdout();
+        stdout.write_all(&rendered)
+            .and_then(|_| stdout.write_all(b"\n"))
+            .and_then(|_| stdout.flush())
+            .map_err(|e| format!("Failed to print response: {}", e))
+    }
+}
+
+// raw and hex encode response messages, which a filtered result no longer is
+fn check_filtered_format(output_format: OutputFormat, filter: &Option<Filter>) -> Result<(), String> {
+    match (output_format, filter) {
+        (OutputFormat::Raw | OutputFormat::Hex, Some(_)) => Err(format!("`{}` output can't be filtered. Clear the filter or choose another format", output_format)),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+use config::{Config, ConfigError, Value, ValueKind};
+use log::LevelFilter;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use crate::grpc_request_dsl::{Network, Port, ServiceName, Target, Timeouts};
+use crate::logger::{self, LogOptions};
+use crate::proxy::{Proxy, ProxyRule};
+use crate::response_format::OutputFormat;
+use crate::retry::RetryPolicy;
+use crate::text_coloring::ColorMode;
+use crate::util::deserialize_duration;
+
+// Settings read from the layered config, checked as a whole before anything runs
+
+const SECTIONS: [&str; 10] = [
+    "log_level",
+    "log",
+    "auto_correction",
+    "defaults",
+    "output",
+    "timeouts",
+    "retry",
+    "descriptor_cache",
+    "proxies",
+    "profiles",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub log_level: LevelFilter,
+    pub log: LogOptions,
+    pub auto_correction: AutoCorrection,
+    pub defaults: TargetDefaults,
+    pub output: Output,
+    pub timeouts: Timeouts,
+    pub retry: RetryPolicy,
+    pub descriptor_cache: DescriptorCache,
+    pub proxies: Vec<ProxyRule>,
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AutoCorrection {
+    // how many rounds of correction an invalid JSON body gets
+    pub max_attempt: usize,
+}
+
+// what the interactive prompts take on `Enter`, and whether a target without a scheme uses TLS
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TargetDefaults {
+    pub host: String,
+    pub port: Port,
+    pub tls: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Output {
+    pub format: OutputFormat,
+    pub color: ColorMode,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DescriptorCache {
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub ttl: Duration,
+}
+
+// a well-known target, with what goes along with calling it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub target: Target,
+    pub metadata: BTreeMap<String, String>,
+    pub default_service: Option<ServiceName>,
+}
+
+// `[profiles.<name>]` as written
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileSettings {
+    host: String,
+    port: Option<Port>,
+    // as a target typed without a scheme would, when not set
+    tls: Option<bool>,
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+    default_service: Option<String>,
+}
+
+impl Settings {
+    pub fn from(config: &Config) -> Result<Settings, String> {
+        let unknown = unknown_sections(config);
+        if !unknown.is_empty() {
+            return Err(format!("Unknown settings: {}", unknown.join(", ")));
+        }
+        let proxies: Vec<ProxyRule> = optional_section(config, "proxies")?.unwrap_or_default();
+        for (i, rule) in proxies.iter().enumerate().filter(|(_, r)| r.url != "direct") {
+            Proxy::from(&rule.url).map_err(|e| format!("Invalid `proxies[{}].url`: {}", i, e))?;
+        }
+        let defaults: TargetDefaults = section(config, "defaults")?;
+        let profiles = match value_at(config, "profiles") {
+            Some(profiles) => profiles.into_table().map_err(|e| format!("Invalid setting: {}", e.extend_with_key("profiles")))?,
+            None => Default::default(),
+        };
+        let profiles = profiles.into_iter()
+            .map(|(name, value)| {
+                let key = format!("profiles.{}", name);
+                deserialized(&key, value).and_then(|profile| profile_of(&key, profile, defaults.tls)).map(|profile| (name, profile))
+            })
+            .collect::<Result<BTreeMap<String, Profile>, String>>()?;
+        Ok(Settings {
+            log_level: logger::level_of(&section::<String>(config, "log_level")?).map_err(|e| invalid(config, "log_level", e))?,
+            log: section(config, "log")?,
+            auto_correction: section(config, "auto_correction")?,
+            defaults,
+            output: section(config, "output")?,
+            timeouts: section(config, "timeouts")?,
+            retry: section(config, "retry")?,
+            descriptor_cache: section(config, "descriptor_cache")?,
+            proxies,
+            profiles,
+        })
+    }
+
+    // how targets are reached, for every call
+    pub fn network(&self) -> Network {
+        Network { default_tls: self.defaults.tls, proxies: self.proxies.clone(), descriptor_cache_ttl: self.descriptor_cache.ttl }
+    }
+}
+
+fn profile_of(key: &str, profile: ProfileSettings, default_tls: bool) -> Result<Profile, String> {
+    let mut target = Target::from_input(&profile.host, default_tls).map_err(|e| format!("Invalid `{}.host`: {}", key, e))?;
+    if profile.port.is_some() {
+        target.port = profile.port;
+    } else if target.port.is_none() && !target.host.is_unix() {
+        return Err(format!("Missing `{}.port`", key));
+    }
+    if let Some(tls) = profile.tls {
+        target.tls = tls;
+    }
+    Ok(Profile { target, metadata: profile.metadata, default_service: profile.default_service.as_deref().map(ServiceName::from) })
+}
+
+// a top level key such as `timeouts` or `log_level`
+pub fn is_section(name: &str) -> bool {
+    SECTIONS.contains(&name)
+}
+
+fn section<T: DeserializeOwned>(config: &Config, key: &str) -> Result<T, String> {
+    optional_section(config, key)?.ok_or_else(|| format!("Missing `{}`", key))
+}
+
+fn optional_section<T: DeserializeOwned>(config: &Config, key: &str) -> Result<Option<T>, String> {
+    value_at(config, key).map(|value| deserialized(key, value)).transpose()
+}
+
+// config names the key and the file of a value of the wrong type, but serde's own errors, of an unknown field
+// or a value failing to parse, come without them. Those are named with the section and the files it came from
+fn deserialized<T: DeserializeOwned>(key: &str, value: Value) -> Result<T, String> {
+    T::deserialize(value.clone()).map_err(|e| match e {
+        ConfigError::Type { origin, unexpected, expected, key: field } => {
+            let key = field.map_or(key.to_string(), |field| key_of(key, &field));
+            format!("Invalid setting: {}", ConfigError::Type { origin, unexpected, expected, key: Some(key) })
+        }
+        e => match origins_of(&value).as_slice() {
+            [] => format!("Invalid `{}`: {}", key, e),
+            origins => format!("Invalid `{}` in {}: {}", key, origins.join(", "), e),
+        },
+    })
+}
+
+// `proxies` and `[0]url`, as config writes a field of an array element, into `proxies[0].url`
+fn key_of(section: &str, field: &str) -> String {
+    let mut key = String::from(section);
+    for c in field.chars() {
+        if (key.ends_with(']') || key == section) && c != '[' && c != '.' {
+            key.push('.');
+        }
+        key.push(c);
+    }
+    key
+}
+
+// `Invalid `log_level` in /etc/zrpc-cli/config.toml: ...`
+fn invalid(config: &Config, key: &str, e: impl std::fmt::Display) -> String {
+    match value_at(config, key).map(|value| origins_of(&value)).unwrap_or_default().as_slice() {
+        [] => format!("Invalid `{}`: {}", key, e),
+        origins => format!("Invalid `{}` in {}: {}", key, origins.join(", "), e),
+    }
+}
+
+// the value at `key` along with where it came from, which `Config::get` drops
+fn value_at(config: &Config, key: &str) -> Option<Value> {
+    key.split('.')
+        .try_fold(&config.cache, |value, k| match &value.kind {
+            ValueKind::Table(table) => table.get(k),
+            _ => None,
+        })
+        .cloned()
+}
+
+// files and the environment the values under `value` came from, leaving out the defaults
+fn origins_of(value: &Value) -> Vec<String> {
+    fn walk(value: &Value, origins: &mut Vec<String>) {
+        match &value.kind {
+            ValueKind::Table(table) => table.values().for_each(|v| walk(v, origins)),
+            ValueKind::Array(array) => array.iter().for_each(|v| walk(v, origins)),
+            _ => match value.origin() {
+                Some(origin) if !origins.iter().any(|o| o == origin) => origins.push(origin.to_string()),
+                _ => {}
+            },
+        }
+    }
+    let mut origins = vec![];
+    walk(value, &mut origins);
+    origins
+}
+
+// with where each of them came from
+fn unknown_sections(config: &Config) -> Vec<String> {
+    match &config.cache.kind {
+        ValueKind::Table(table) => table.iter()
+            .filter(|(k, _)| !is_section(k))
+            .map(|(k, v)| match origins_of(v).as_slice() {
+                [] => format!("`{}`", k),
+                origins => format!("`{}` in {}", k, origins.join(", ")),
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::{File, FileFormat};
+    use crate::logger::{Destination, LogFormat};
+
+    fn config_of(toml: &str) -> Config {
+        Config::builder()
+            .add_source(File::from_str(crate::config_loader::DEFAULTS, FileFormat::Toml))
+            .add_source(File::from_str(toml, FileFormat::Toml))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn typed_settings() {
+        let settings = Settings::from(&config_of("log_level = \"debug\"\n[defaults]\nport = 50051\ntls = true\n[timeouts]\ndeadline = \"0\"")).unwrap();
+        assert_eq!(settings.log_level, LevelFilter::Debug);
+        assert_eq!(settings.defaults, TargetDefaults { host: String::from("localhost"), port: Port(50051), tls: true });
+        assert_eq!(settings.timeouts, Timeouts { connect: Duration::from_secs(10), deadline: None });
+        assert_eq!(settings.auto_correction.max_attempt, 5);
+        assert_eq!((settings.log.destination, settings.log.max_size), (Destination::Stderr, 10 * 1024 * 1024));
+
+        let log = Settings::from(&config_of("[log]\nformat = \"json\"\nmax_size = \"512KB\"\n[log.modules]\nrustls = \"warn\"\n\"zrpc_cli::proxy\" = \"trace\"")).unwrap().log;
+        assert_eq!((log.format, log.max_size), (LogFormat::Json, 512 * 1024));
+        assert_eq!(log.modules, vec![(String::from("rustls"), LevelFilter::Warn), (String::from("zrpc_cli::proxy"), LevelFilter::Trace)]);
+    }
+
+    #[test]
+    fn profiles() {
+        let settings = Settings::from(&config_of(r#"
+            defaults.tls = true
+            [profiles.payments-staging]
+            host = "payments.staging"
+            port = 443
+            metadata = { authorization = "Bearer token" }
+            default_service = "payments.v1.Payments"
+            [profiles.local]
+            host = "http://localhost:50051"
+        "#)).unwrap();
+        let staging = &settings.profiles["payments-staging"];
+        assert_eq!((staging.target.to_string().as_str(), staging.default_service.as_ref().map(|s| s.0.as_str())), ("grpcs://payments.staging:443", Some("payments.v1.Payments")));
+        assert_eq!(staging.metadata, BTreeMap::from([(String::from("authorization"), String::from("Bearer token"))]));
+        assert!(!settings.profiles["local"].target.tls);
+
+        assert_eq!(Settings::from(&config_of("[profiles.api]\nhost = \"api\"")), Err(String::from("Missing `profiles.api.port`")));
+        assert_eq!(Settings::from(&config_of("[profiles.api]\nhost = \"api:443\"\nservice = \"a.B\"")).unwrap_err(),
+                   "Invalid `profiles.api`: unknown field `service`, expected one of `host`, `port`, `tls`, `metadata`, `default_service`");
+    }
+
+    #[test]
+    fn bad_settings_are_named() {
+        let error = |toml: &str| Settings::from(&config_of(toml)).unwrap_err();
+        assert_eq!(error("tokn = \"secret\""), "Unknown settings: `tokn`");
+        assert_eq!(error("[timeouts]\nconect = \"3s\""), "Invalid `timeouts`: unknown field `conect`, expected `connect` or `deadline`");
+        assert_eq!(error("[retry]\ninitial_backoff = \"soon\""), "Invalid `retry`: Invalid duration `soon`. ex) 500ms, 30s, 5m");
+        assert!(error("[defaults]\ntls = \"maybe\"").contains("for key `defaults.tls`"));
+        assert_eq!(error("[[proxies]]\ntarget = \"api\"\nurl = [\"http://proxy:3128\"]"),
+                   "Invalid setting: invalid type: sequence, expected a string for key `proxies[0].url`");
+        assert_eq!(error("[[proxies]]\ntarget = \"api\""), "Invalid `proxies`: missing field `url`");
+    }
+
+    #[test]
+    fn no_attempt_is_rejected() {
+        let error = |toml: &str| Settings::from(&config_of(toml)).unwrap_err();
+        assert_eq!(error("[retry]\nmax_attempts = 0"), "Invalid `retry`: `max_attempts` must be at least 1");
+        assert!(error("[retry]\nmax_attempts = -1").contains("for key `retry.max_attempts`"));
+        assert_eq!(Settings::from(&config_of("[retry]\nmax_attempts = 3")).unwrap().retry.max_attempts, 3);
+    }
+}
@@ -9,11 +9,17 @@ use log::*;
 #[derive(Debug)]
 pub struct SmartParser {
     pub origin: String,
+    max_attempt: usize,
 }
 
 impl SmartParser {
     pub fn new(s: &str) -> SmartParser {
-        SmartParser { origin: String::from(s) }
+        SmartParser { origin: String::from(s), max_attempt: 3 }
+    }
+
+    // rounds of correction, `auto_correction.max_attempt` in config
+    pub fn with_max_attempt(self, max_attempt: usize) -> SmartParser {
+        SmartParser { max_attempt, ..self }
     }
 
     fn try_auto_correction(parts: Vec<JsonPart>, tries: usize) -> Result<Value, String> {
@@ -51,7 +57,7 @@ impl SmartParser {
             Err(_) => {
                 Self::try_auto_correction(
                     JsonPartStack::translate_to_parts(self.origin.as_str()),
-                    3,
+                    self.max_attempt,
                 )
             }
         }
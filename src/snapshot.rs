@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use serde_json::Value;
+use crate::collection::{self, CollectionEntry};
+use crate::grpc_client;
+use crate::grpc_request_dsl::{Network, Timeouts};
+use crate::retry::RetryPolicy;
+use crate::json_diff::{self, JsonPath, PathSegment};
+use crate::text_coloring::{to_error, to_success, to_warn};
+
+#[derive(Debug, PartialEq)]
+pub enum SnapshotOutcome {
+    Matched,
+    Recorded,
+    Mismatched(Vec<String>),
+    Failed(String),
+}
+
+pub fn run(collection_path: &str, dir: &str, ignore: &[String], update: bool, timeouts: Timeouts, retry_policy: &RetryPolicy, network: &Network) -> Result<bool, String> {
+    let entries = collection::load(collection_path)?;
+    // ignore paths are written relative to a single message
+    let ignore_paths = ignore.iter()
+        .map(|p| JsonPath::from(p).map(|path| JsonPath([vec![PathSegment::Any], path.0].concat())))
+        .collect::<Result<Vec<JsonPath>, String>>()?;
+    check_golden_files(&entries, Path::new(dir))?;
+    if update {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create snapshot directory `{}`: {}", dir, e))?;
+    }
+
+    let mut all_passed = true;
+    for entry in &entries {
+        let outcome = snapshot_entry(entry, Path::new(dir), &ignore_paths, update, timeouts, retry_policy, network);
+        match &outcome {
+            SnapshotOutcome::Matched => println!("{} {}", to_success("PASS"), entry.name),
+            SnapshotOutcome::Recorded => println!("{} {}", to_warn("SAVED"), entry.name),
+            SnapshotOutcome::Mismatched(differences) => {
+                println!("{} {}", to_error("FAIL"), entry.name);
+                differences.iter().for_each(|d| println!("\t{}", d));
+            }
+            SnapshotOutcome::Failed(reason) => println!("{} {}: {}", to_error("ERROR"), entry.name, reason),
+        }
+        all_passed &= matches!(outcome, SnapshotOutcome::Matched | SnapshotOutcome::Recorded);
+    }
+    Ok(all_passed)
+}
+
+fn snapshot_entry(entry: &CollectionEntry, dir: &Path, ignore: &[JsonPath], update: bool, timeouts: Timeouts, retry_policy: &RetryPolicy, network: &Network) -> SnapshotOutcome {
+    let golden_file = golden_file_path(dir, &entry.name);
+    // recorded only when asked, so a fresh checkout or a wrong `--dir` doesn't pass
+    if !update && !golden_file.exists() {
+        return SnapshotOutcome::Failed(format!("Missing golden file `{}`. Run with `--update` to record it", golden_file.display()));
+    }
+    let sent = entry.to_service_request().and_then(|mut r| {
+        r.update_timeouts(timeouts);
+        r.update_retry_policy(retry_policy.clone());
+        r.update_network(network.clone());
+        grpc_client::request(&r)
+    });
+    let actual = match sent {
+        Ok(response) if response.status.is_ok() => Value::Array(response.messages),
+        Ok(response) => return SnapshotOutcome::Failed(response.status.to_string()),
+        Err(e) => return SnapshotOutcome::Failed(e),
+    };
+
+    if update {
+        let pretty = serde_json::to_string_pretty(&actual).unwrap_or_default();
+        return match std::fs::write(&golden_file, pretty + "\n") {
+            Ok(_) => SnapshotOutcome::Recorded,
+            Err(e) => SnapshotOutcome::Failed(format!("Failed to write `{}`: {}", golden_file.display(), e)),
+        };
+    }
+
+    let expected = match std::fs::read_to_string(&golden_file)
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str::<Value>(&s).map_err(|e| e.to_string())) {
+        Ok(v) => v,
+        Err(e) => return SnapshotOutcome::Failed(format!("Failed to read `{}`: {}", golden_file.display(), e)),
+    };
+
+    let differences = json_diff::diff(&expected, &actual, ignore);
+    if differences.is_empty() {
+        SnapshotOutcome::Matched
+    } else {
+        SnapshotOutcome::Mismatched(differences.iter().map(|d| d.to_string()).collect())
+    }
+}
+
+// names differing only in characters a file name can't have would share a golden file
+fn check_golden_files(entries: &[CollectionEntry], dir: &Path) -> Result<(), String> {
+    let mut names_of: HashMap<PathBuf, &str> = HashMap::new();
+    for entry in entries {
+        let path = golden_file_path(dir, &entry.name);
+        if let Some(other) = names_of.insert(path.clone(), &entry.name) {
+            return Err(format!("Entries `{}` and `{}` would share the golden file `{}`. Rename one of them", other, entry.name, path.display()));
+        }
+    }
+    Ok(())
+}
+
+fn golden_file_path(dir: &Path, name: &str) -> PathBuf {
+    let file_name: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect();
+    dir.join(format!("{}.json", file_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str) -> CollectionEntry {
+        serde_json::from_value(serde_json::json!({"name": name, "host": "localhost", "port": 50051, "service": "a.B", "function": "C"})).unwrap()
+    }
+
+    #[test]
+    fn names_sharing_a_golden_file() {
+        let dir = Path::new("snapshots");
+        assert_eq!(check_golden_files(&[entry("a-b"), entry("a_b")], dir), Ok(()));
+        let error = check_golden_files(&[entry("a/b"), entry("c"), entry("a_b")], dir).unwrap_err();
+        assert!(error.starts_with("Entries `a/b` and `a_b` would share the golden file"));
+    }
+}
@@ -0,0 +1,414 @@
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+use prost_reflect::{MessageDescriptor, MethodDescriptor};
+use serde_json::{json, Value};
+use crate::descriptors;
+use crate::grpc_client;
+use crate::grpc_request_dsl::{Host, Protocol, ServiceRequest, Target};
+use crate::util::to_json_stream;
+
+// The current request written for tools and languages without zrpc-cli
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SnippetFormat {
+    Grpcurl,
+    BufCurl,
+    Rust,
+    Go,
+    Python,
+}
+
+impl SnippetFormat {
+    pub const ALL: [SnippetFormat; 5] = [SnippetFormat::Grpcurl, SnippetFormat::BufCurl, SnippetFormat::Rust, SnippetFormat::Go, SnippetFormat::Python];
+
+    pub fn description(&self) -> &str {
+        match self {
+            SnippetFormat::Grpcurl => "command line using reflection",
+            SnippetFormat::BufCurl => "command line using reflection",
+            SnippetFormat::Rust => "tonic client, with messages generated by tonic-build and pbjson-build",
+            SnippetFormat::Go => "grpc-go client, with messages generated by protoc-gen-go",
+            SnippetFormat::Python => "grpcio client, with messages generated by grpcio-tools",
+        }
+    }
+}
+
+impl Display for SnippetFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnippetFormat::Grpcurl => write!(f, "grpcurl"),
+            SnippetFormat::BufCurl => write!(f, "buf curl"),
+            SnippetFormat::Rust => write!(f, "Rust"),
+            SnippetFormat::Go => write!(f, "Go"),
+            SnippetFormat::Python => write!(f, "Python"),
+        }
+    }
+}
+
+pub fn render(format: SnippetFormat, request: &ServiceRequest) -> Result<String, String> {
+    let bodies = bodies_of(&request.body.0)?;
+    if request.protocol != Protocol::Grpc && format != SnippetFormat::BufCurl {
+        return Err(format!("{} only speaks gRPC, not {}. buf curl speaks both", format, request.protocol));
+    }
+    match format {
+        SnippetFormat::Grpcurl => Ok(grpcurl(request, &bodies)),
+        SnippetFormat::BufCurl => Ok(buf_curl(request, &bodies)),
+        // code needs the generated type names
+        SnippetFormat::Rust => rust(request, &descriptors::method_of(request)?, &bodies),
+        SnippetFormat::Go => Ok(go(request, &descriptors::method_of(request)?, &bodies)),
+        SnippetFormat::Python => Ok(python(request, &descriptors::method_of(request)?, &bodies)),
+    }
+}
+
+// each message of the body, compacted. An empty body is an empty message
+fn bodies_of(body: &str) -> Result<Vec<String>, String> {
+    let values = to_json_stream(body).map_err(|e| format!("Invalid request body: {}", e))?;
+    if values.is_empty() {
+        return Ok(vec![json!({}).to_string()]);
+    }
+    Ok(values.iter().map(Value::to_string).collect())
+}
+
+// POSIX shell single quoting. `'` can't be escaped inside, so it closes, escapes and reopens the quote
+pub fn shell_quote(s: &str) -> String {
+    let plain = !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || "_-./:@=,+%".contains(c));
+    if plain { String::from(s) } else { format!("'{}'", s.replace('\'', r"'\''")) }
+}
+
+fn seconds(d: Duration) -> String {
+    d.as_secs_f64().to_string()
+}
+
+fn grpcurl(request: &ServiceRequest, bodies: &[String]) -> String {
+    let mut args = vec![String::from("grpcurl")];
+    args.extend(grpc_client::grpcurl_timeout_args(&request.timeouts));
+    args.extend(request.metadata.iter().flat_map(|(k, v)| [String::from("-H"), shell_quote(&format!("{}: {}", k, v))]));
+    args.extend(request.schema.protosets.iter().flat_map(|p| [String::from("-protoset"), shell_quote(p)]));
+    args.extend(request.schema.import_paths.iter().flat_map(|p| [String::from("-import-path"), shell_quote(p)]));
+    args.extend(request.schema.protos.iter().flat_map(|p| [String::from("-proto"), shell_quote(p)]));
+    args.extend([String::from("-d"), shell_quote(&bodies.join("\n"))]);
+    args.extend(grpc_client::grpcurl_target_args(&request.target()).iter().map(|a| shell_quote(a)));
+    args.push(shell_quote(&format!("{}/{}", request.service_name, request.service_function)));
+    args.join(" ")
+}
+
+fn buf_curl(request: &ServiceRequest, bodies: &[String]) -> String {
+    let target = request.target();
+    let protocol = match target.protocol {
+        Protocol::Grpc => "grpc",
+        Protocol::GrpcWeb | Protocol::GrpcWebText => "grpcweb",
+        Protocol::ConnectJson | Protocol::ConnectProto => "connect",
+    };
+    let mut args = vec![String::from("buf curl"), format!("--protocol {}", protocol)];
+    if !target.tls && target.protocol == Protocol::Grpc {
+        args.push(String::from("--http2-prior-knowledge"));
+    }
+    args.push(format!("--connect-timeout {}", seconds(request.timeouts.connect)));
+    let authority = match &target.host {
+        Host::Unix(path) => {
+            args.push(format!("--unix-socket {}", shell_quote(path)));
+            String::from("localhost")
+        }
+        Host::UnixAbstract(name) => {
+            args.push(format!("--unix-socket {}", shell_quote(&format!("@{}", name))));
+            String::from("localhost")
+        }
+        Host::Dns { name, .. } => format!("{}:{}", name, request.port.0),
+        host => format!("{}:{}", host, request.port.0),
+    };
+    args.extend(request.metadata.iter().map(|(k, v)| format!("-H {}", shell_quote(&format!("{}: {}", k, v)))));
+    args.push(format!("--data {}", shell_quote(&bodies.join("\n"))));
+    let scheme = if target.tls { "https" } else { "http" };
+    args.push(shell_quote(&format!("{}://{}/{}/{}", scheme, authority, request.service_name, request.service_function)));
+    args.join(" \\\n  ")
+}
+
+// what the gRPC name resolvers of Go and Python dial
+fn dial_target(target: &Target) -> String {
+    match (&target.host, &target.port) {
+        (host, _) if host.is_unix() => host.to_string(),
+        (host, Some(port)) => format!("{}:{}", host, port.0),
+        (host, None) => host.to_string(),
+    }
+}
+
+// as prost names modules and methods. ex) `GetHTTPStatus` -> `get_http_status`
+fn to_snake_case(name: &str) -> String {
+    let chars = name.chars().collect::<Vec<char>>();
+    let mut snake = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() {
+            let boundary = i > 0 && (!chars[i - 1].is_ascii_uppercase() || chars.get(i + 1).is_some_and(|n| n.is_ascii_lowercase()));
+            if boundary && !snake.ends_with('_') {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(*c);
+        }
+    }
+    snake
+}
+
+// name of a message relative to its package. ex) `Outer.Inner`
+fn relative_name(message: &MessageDescriptor) -> String {
+    let package = message.package_name();
+    let full_name = message.full_name();
+    if package.is_empty() { String::from(full_name) } else { String::from(&full_name[package.len() + 1..]) }
+}
+
+// `r#"..."#` with as many `#` as it takes
+fn rust_string(s: &str) -> String {
+    let hashes = "#".repeat((1..).find(|n| !s.contains(&format!("\"{}", "#".repeat(*n)))).unwrap_or(1));
+    format!("r{}\"{}\"{}", hashes, s, hashes)
+}
+
+fn rust(request: &ServiceRequest, method: &MethodDescriptor, bodies: &[String]) -> Result<String, String> {
+    let target = request.target();
+    let scheme = if target.tls { "https" } else { "http" };
+    let address = match &target.host {
+        Host::Name(name) | Host::Dns { name } => format!("{}://{}:{}", scheme, name, request.port.0),
+        Host::Ipv6(address) => format!("{}://[{}]:{}", scheme, address, request.port.0),
+        host => return Err(format!("Rust snippets can't dial `{}`", host)),
+    };
+    let package = method.parent_service().package_name().to_string();
+    let module = if package.is_empty() { String::from("proto") } else { package.replace('.', "::") };
+    let service = method.parent_service().name().to_string();
+    // nested messages live in a module named after their parent
+    let relative = relative_name(&method.input());
+    let input = relative.split('.').collect::<Vec<_>>();
+    let input = input.iter().enumerate()
+        .map(|(i, part)| if i + 1 < input.len() { to_snake_case(part) } else { part.to_string() })
+        .collect::<Vec<_>>().join("::");
+
+    let mut code = vec![
+        String::from("// [dependencies] tonic, prost, tokio, serde_json, with messages built by tonic-build and pbjson-build"),
+        format!("pub mod {} {{", module.split("::").next().unwrap_or("proto")),
+    ];
+    let nested = module.split("::").skip(1).collect::<Vec<_>>();
+    nested.iter().enumerate().for_each(|(i, m)| code.push(format!("{}pub mod {} {{", "    ".repeat(i + 1), m)));
+    let depth = nested.len() + 1;
+    // files of messages without a package are named `_`
+    let generated = if package.is_empty() { "_" } else { &package };
+    code.push(format!("{}tonic::include_proto!(\"{}\");", "    ".repeat(depth), generated));
+    code.push(format!("{}include!(concat!(env!(\"OUT_DIR\"), \"/{}.serde.rs\"));", "    ".repeat(depth), generated));
+    (0..depth).rev().for_each(|i| code.push(format!("{}}}", "    ".repeat(i))));
+    code.push(format!("use {}::{}_client::{}Client;", module, to_snake_case(&service), service));
+    code.push(String::new());
+    code.push(String::from("#[tokio::main]"));
+    code.push(String::from("async fn main() -> Result<(), Box<dyn std::error::Error>> {"));
+    code.push(format!("    let channel = tonic::transport::Endpoint::from_static(\"{}\")", address));
+    code.push(format!("        .connect_timeout(std::time::Duration::from_millis({}))", request.timeouts.connect.as_millis()));
+    if target.tls {
+        code.push(String::from("        .tls_config(tonic::transport::ClientTlsConfig::new().with_native_roots())?"));
+    }
+    code.push(String::from("        .connect().await?;"));
+    code.push(format!("    let mut client = {}Client::new(channel);", service));
+    let input_package = method.input().package_name().replace('.', "::");
+    let input = if input_package.is_empty() { format!("{}::{}", module, input) } else { format!("{}::{}", input_package, input) };
+    let messages = bodies.iter().map(|b| format!("serde_json::from_str::<{}>({})?", input, rust_string(b))).collect::<Vec<_>>();
+    if method.is_client_streaming() {
+        code.push(format!("    let messages = vec![\n        {},\n    ];", messages.join(",\n        ")));
+        code.push(String::from("    let mut request = tonic::Request::new(tokio_stream::iter(messages));"));
+    } else {
+        code.push(format!("    let mut request = tonic::Request::new({});", messages[0]));
+    }
+    if let Some(deadline) = request.timeouts.deadline {
+        code.push(format!("    request.set_timeout(std::time::Duration::from_millis({}));", deadline.as_millis()));
+    }
+    let call = format!("client.{}(request).await?", to_snake_case(method.name()));
+    if method.is_server_streaming() {
+        code.push(format!("    let mut stream = {}.into_inner();", call));
+        code.push(String::from("    while let Some(message) = stream.message().await? {"));
+        code.push(String::from("        println!(\"{}\", serde_json::to_string_pretty(&message)?);"));
+        code.push(String::from("    }"));
+    } else {
+        code.push(format!("    let response = {};", call));
+        code.push(String::from("    println!(\"{}\", serde_json::to_string_pretty(response.get_ref())?);"));
+    }
+    code.push(String::from("    Ok(())"));
+    code.push(String::from("}"));
+    Ok(code.join("\n"))
+}
+
+// a raw string unless it holds a backquote
+fn go_string(s: &str) -> String {
+    if s.contains('`') { Value::String(String::from(s)).to_string() } else { format!("`{}`", s) }
+}
+
+fn go(request: &ServiceRequest, method: &MethodDescriptor, bodies: &[String]) -> String {
+    let target = request.target();
+    let service = method.parent_service().name().to_string();
+    let input = relative_name(&method.input()).replace('.', "_");
+    let file = method.parent_service().parent_file().name().to_string();
+    let (client_streaming, server_streaming) = (method.is_client_streaming(), method.is_server_streaming());
+
+    let mut imports = vec!["\"context\"", "\"fmt\"", "\"log\""];
+    if server_streaming { imports.push("\"io\""); }
+    if request.timeouts.deadline.is_some() { imports.push("\"time\""); }
+    if target.tls { imports.push("\"crypto/tls\""); }
+    imports.sort();
+    let mut code = vec![String::from("package main"), String::new(), String::from("import (")];
+    imports.iter().for_each(|i| code.push(format!("\t{}", i)));
+    code.push(String::new());
+    code.push(String::from("\t\"google.golang.org/grpc\""));
+    code.push(String::from(if target.tls { "\t\"google.golang.org/grpc/credentials\"" } else { "\t\"google.golang.org/grpc/credentials/insecure\"" }));
+    code.push(String::from("\t\"google.golang.org/protobuf/encoding/protojson\""));
+    code.push(String::new());
+    code.push(format!("\tpb \"example.com/your/module/gen\" // generated from {}", file));
+    code.push(String::from(")"));
+    code.push(String::new());
+    code.push(String::from("func main() {"));
+    let credentials = if target.tls { "credentials.NewTLS(&tls.Config{})" } else { "insecure.NewCredentials()" };
+    code.push(format!("\tconn, err := grpc.NewClient({}, grpc.WithTransportCredentials({}))", Value::String(dial_target(&target)), credentials));
+    code.push(String::from("\tif err != nil {\n\t\tlog.Fatal(err)\n\t}"));
+    code.push(String::from("\tdefer conn.Close()"));
+    code.push(format!("\tclient := pb.New{}Client(conn)", service));
+    match request.timeouts.deadline {
+        Some(deadline) => code.push(format!("\tctx, cancel := context.WithTimeout(context.Background(), {}*time.Millisecond)", deadline.as_millis())),
+        None => code.push(String::from("\tctx, cancel := context.WithCancel(context.Background())")),
+    }
+    code.push(String::from("\tdefer cancel()"));
+    code.push(String::new());
+
+    let unmarshal = |body: &str, indent: &str| format!(
+        "{i}req := &pb.{}{{}}\n{i}if err := protojson.Unmarshal([]byte({}), req); err != nil {{\n{i}\tlog.Fatal(err)\n{i}}}",
+        input, body, i = indent);
+    let print = |indent: &str| format!("{}fmt.Println(protojson.Format(res))", indent);
+    let check = |indent: &str| format!("{i}if err != nil {{\n{i}\tlog.Fatal(err)\n{i}}}", i = indent);
+    if client_streaming {
+        code.push(format!("\tstream, err := client.{}(ctx)", method.name()));
+        code.push(check("\t"));
+        code.push(String::from("\tfor _, body := range []string{"));
+        bodies.iter().for_each(|b| code.push(format!("\t\t{},", go_string(b))));
+        code.push(String::from("\t} {"));
+        code.push(unmarshal("body", "\t\t"));
+        code.push(String::from("\t\tif err := stream.Send(req); err != nil {\n\t\t\tlog.Fatal(err)\n\t\t}"));
+        code.push(String::from("\t}"));
+        if server_streaming {
+            code.push(String::from("\tif err := stream.CloseSend(); err != nil {\n\t\tlog.Fatal(err)\n\t}"));
+        } else {
+            code.push(String::from("\tres, err := stream.CloseAndRecv()"));
+            code.push(check("\t"));
+            code.push(print("\t"));
+        }
+    } else {
+        code.push(unmarshal(&go_string(&bodies[0]), "\t"));
+        if server_streaming {
+            code.push(format!("\tstream, err := client.{}(ctx, req)", method.name()));
+        } else {
+            code.push(format!("\tres, err := client.{}(ctx, req)", method.name()));
+        }
+        code.push(check("\t"));
+        if !server_streaming {
+            code.push(print("\t"));
+        }
+    }
+    if server_streaming {
+        code.push(String::from("\tfor {"));
+        code.push(String::from("\t\tres, err := stream.Recv()"));
+        code.push(String::from("\t\tif err == io.EOF {\n\t\t\tbreak\n\t\t}"));
+        code.push(check("\t\t"));
+        code.push(print("\t\t"));
+        code.push(String::from("\t}"));
+    }
+    code.push(String::from("}"));
+    code.join("\n")
+}
+
+// single quoted as JSON is mostly double quotes
+fn python_string(s: &str) -> String {
+    if s.contains(['\'', '\\']) { Value::String(String::from(s)).to_string() } else { format!("'{}'", s) }
+}
+
+fn python(request: &ServiceRequest, method: &MethodDescriptor, bodies: &[String]) -> String {
+    let target = request.target();
+    let service = method.parent_service().name().to_string();
+    // grpcio-tools names modules after the file. ex) `foo/bar.proto` -> `foo.bar_pb2`
+    let file = method.parent_service().parent_file().name().to_string();
+    let module = file.trim_end_matches(".proto").replace(['/', '-'], ".");
+    let (package, name) = match module.rsplit_once('.') {
+        Some((package, name)) => (format!("from {} ", package), name.to_string()),
+        None => (String::new(), module.clone()),
+    };
+    let input_module = method.input().parent_file().name().trim_end_matches(".proto").replace(['/', '-'], ".");
+    let input_name = input_module.rsplit('.').next().unwrap_or(&input_module).to_string();
+
+    let mut code = vec![String::from("import grpc"), String::from("from google.protobuf import json_format")];
+    code.push(format!("{}import {}_pb2_grpc", package, name));
+    match input_module.rsplit_once('.') {
+        Some((input_package, _)) => code.push(format!("from {} import {}_pb2", input_package, input_name)),
+        None => code.push(format!("import {}_pb2", input_name)),
+    }
+    code.push(String::new());
+    let address = Value::String(dial_target(&target));
+    if target.tls {
+        code.push(format!("channel = grpc.secure_channel({}, grpc.ssl_channel_credentials())", address));
+    } else {
+        code.push(format!("channel = grpc.insecure_channel({})", address));
+    }
+    code.push(format!("stub = {}_pb2_grpc.{}Stub(channel)", name, service));
+    let message = |body: &str| format!("json_format.Parse({}, {}_pb2.{}())", python_string(body), input_name, relative_name(&method.input()));
+    if method.is_client_streaming() {
+        code.push(String::from("requests = ["));
+        bodies.iter().for_each(|b| code.push(format!("    {},", message(b))));
+        code.push(String::from("]"));
+    } else {
+        code.push(format!("request = {}", message(&bodies[0])));
+    }
+    let argument = if method.is_client_streaming() { "iter(requests)" } else { "request" };
+    let timeout = request.timeouts.deadline.map_or(String::new(), |d| format!(", timeout={}", seconds(d)));
+    let call = format!("stub.{}({}{})", method.name(), argument, timeout);
+    if method.is_server_streaming() {
+        code.push(format!("for response in {}:", call));
+        code.push(String::from("    print(json_format.MessageToJson(response))"));
+    } else {
+        code.push(format!("response = {}", call));
+        code.push(String::from("print(json_format.MessageToJson(response))"));
+    }
+    code.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_reflect::DescriptorPool;
+    use crate::grpc_request_dsl::Network;
+
+    #[test]
+    fn quote_for_shell() {
+        assert_eq!(shell_quote("localhost:9090"), "localhost:9090");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote(r#"{"name":"John"}"#), r#"'{"name":"John"}'"#);
+        assert_eq!(shell_quote(r#"{"name":"O'Brien"}"#), r#"'{"name":"O'\''Brien"}'"#);
+        assert_eq!(shell_quote("$HOME"), "'$HOME'");
+    }
+
+    #[test]
+    fn grpcurl_command() {
+        let mut request = ServiceRequest::parse("localhost:9090", "helloworld.Greeter/SayHello", Network::default()).unwrap();
+        request.update_body(String::from("{\"name\": \"It's me\"}"));
+        let bodies = bodies_of(&request.body.0).unwrap();
+        assert_eq!(grpcurl(&request, &bodies),
+                   r#"grpcurl -connect-timeout 10 -max-time 30 -d '{"name":"It'\''s me"}' -plaintext localhost:9090 helloworld.Greeter/SayHello"#);
+    }
+
+    #[test]
+    fn names_in_generated_code() {
+        assert_eq!(to_snake_case("SayHello"), "say_hello");
+        assert_eq!(to_snake_case("GetHTTPStatus"), "get_http_status");
+        assert_eq!(to_snake_case("list_v2"), "list_v2");
+        assert_eq!(rust_string(r##"{"a":"\"#"}"##), r###"r##"{"a":"\"#"}"##"###);
+    }
+
+    #[test]
+    fn go_imports_time_for_a_deadline_only() {
+        let pool = DescriptorPool::decode(tonic_reflection::pb::v1::FILE_DESCRIPTOR_SET).unwrap();
+        let method = pool.get_service_by_name("grpc.reflection.v1.ServerReflection").and_then(|s| s.methods().next()).unwrap();
+        let mut request = ServiceRequest::parse("localhost:9090", "grpc.reflection.v1.ServerReflection/ServerReflectionInfo", Network::default()).unwrap();
+        let bodies = vec![String::from(r#"{"listServices": ""}"#)];
+        assert!(go(&request, &method, &bodies).contains("\t\"time\"\n"));
+        request.timeouts.deadline = None;
+        let code = go(&request, &method, &bodies);
+        assert!(!code.contains("\"time\""), "{}", code);
+        assert!(code.contains("context.WithCancel(context.Background())"));
+    }
+}
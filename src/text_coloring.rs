@@ -1,4 +1,42 @@
+use std::io::IsTerminal;
 use colored::{ColoredString, Colorize};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ColorMode {
+    // colored on a terminal, unless `NO_COLOR` is set
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    pub fn from(s: &str) -> Result<ColorMode, String> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(ColorMode::Auto),
+            "always" => Ok(ColorMode::Always),
+            "never" => Ok(ColorMode::Never),
+            _ => Err(format!("Invalid color `{}`. auto, always or never", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for ColorMode {
+    type Error = String;
+    fn try_from(s: String) -> Result<ColorMode, String> {
+        ColorMode::from(&s)
+    }
+}
+
+pub fn set_color_mode(mode: ColorMode) {
+    match mode {
+        ColorMode::Auto if !std::io::stdout().is_terminal() => colored::control::set_override(false),
+        ColorMode::Auto => {}
+        ColorMode::Always => colored::control::set_override(true),
+        ColorMode::Never => colored::control::set_override(false),
+    }
+}
 
 pub fn to_success(text: &str) -> ColoredString {
     text.green()
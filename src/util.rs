@@ -1,4 +1,5 @@
 use std::io::BufRead;
+use serde::{de, Deserialize, Deserializer};
 use serde_json::{json, Value};
 use crate::user_input;
 use user_input::*;
@@ -11,7 +12,7 @@ pub fn parse_usize(str: String, max_bound: &usize) -> Result<usize, String> {
 }
 
 pub fn to_json(string_json: &String) -> Result<Value, String>{
-    let empty: Value = json!("{}");
+    let empty: Value = json!({});
     if string_json.is_empty() { Ok(empty) } else {
         serde_json::from_str(string_json.as_str()).map_err(|e|e.to_string())
     }
@@ -49,4 +50,87 @@ pub fn emptiable_input(user_input: &mut UserInput, default: &str) -> () {
     std::io::stdin().read_line(&mut temp_string).unwrap();
     temp_string = String::from(temp_string.to_string().trim()); // trim
     if temp_string.is_empty() { user_input.push(String::from(default))} else { user_input.push(temp_string) }
-}
\ No newline at end of file
+}
+
+pub fn to_json_stream(string_json: &str) -> Result<Vec<Value>, String> {
+    serde_json::Deserializer::from_str(string_json)
+        .into_iter::<Value>()
+        .collect::<Result<Vec<Value>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+// `1500ms`, `30s`, `5m`, `1h` or plain seconds
+pub fn parse_duration(s: &str) -> Result<std::time::Duration, String> {
+    let trimmed = s.trim();
+    let split_at = trimmed.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split_at);
+    let invalid = || format!("Invalid duration `{}`. ex) 500ms, 30s, 5m", s);
+    let value = number.parse::<f64>().map_err(|_| invalid())?;
+    let seconds = match unit {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        _ => return Err(format!("Invalid duration unit `{}`. Expected one of ms, s, m, h", unit)),
+    };
+    // negative, NaN, infinite or too long
+    std::time::Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
+
+// `512KB`, `10MB`, `1GB` or plain bytes
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let trimmed = s.trim();
+    let split_at = trimmed.find(|c: char| !c.is_ascii_digit()).unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split_at);
+    let value = number.parse::<u64>().map_err(|_| format!("Invalid size `{}`. ex) 512KB, 10MB", s))?;
+    let unit_size = match unit.trim().to_uppercase().as_str() {
+        "" | "B" => 1,
+        "KB" => 1024,
+        "MB" => 1024 * 1024,
+        "GB" => 1024 * 1024 * 1024,
+        _ => return Err(format!("Invalid size unit `{}`. Expected one of B, KB, MB, GB", unit)),
+    };
+    value.checked_mul(unit_size).ok_or_else(|| format!("Too large size `{}`", s))
+}
+
+// a setting written as a string, for `#[serde(deserialize_with)]`
+pub fn deserialize_parsed<'de, D: Deserializer<'de>, T>(deserializer: D, parse: impl Fn(&str) -> Result<T, String>) -> Result<T, D::Error> {
+    parse(&String::deserialize(deserializer)?).map_err(de::Error::custom)
+}
+
+pub fn deserialize_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<std::time::Duration, D::Error> {
+    deserialize_parsed(deserializer, parse_duration)
+}
+
+pub fn deserialize_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    deserialize_parsed(deserializer, parse_size)
+}
+
+// where state kept between sessions lives. ex) `~/.local/state/zrpc-cli`
+pub fn state_dir() -> Result<std::path::PathBuf, String> {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|d| d.join("zrpc-cli"))
+        .ok_or_else(|| String::from("No directory to keep state in"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_body_is_an_empty_message() {
+        assert_eq!(to_json(&String::new()), Ok(json!({})));
+        assert_eq!(to_json(&String::new()).unwrap().to_string(), "{}");
+        assert_eq!(to_json(&String::from(r#"{"name": "a"}"#)), Ok(json!({"name": "a"})));
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("1500ms"), Ok(std::time::Duration::from_millis(1500)));
+        assert_eq!(parse_duration("2m"), Ok(std::time::Duration::from_secs(120)));
+        for s in ["99999999999999999999999h", "-1s", "NaN", "inf", "1e400", "soon"] {
+            assert!(parse_duration(s).unwrap_err().starts_with("Invalid duration"), "{}", s);
+        }
+    }
+}
@@ -0,0 +1,646 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::Instant;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig, STANDARD};
+use base64::engine::DecodePaddingMode;
+use base64::Engine;
+use bytes::{Buf, Bytes, BytesMut};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper_util::rt::TokioIo;
+use log::debug;
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor, MethodDescriptor};
+use prost_types::{FileDescriptorProto, FileDescriptorSet};
+use serde_json::{json, Value};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::rustls;
+use tonic::codegen::http::{self, HeaderMap, HeaderName, HeaderValue};
+use tonic::metadata::MetadataMap;
+use crate::grpc_client;
+use crate::proxy;
+use crate::grpc_request_dsl::{Host, Network, Protocol, Target, Timeouts};
+use crate::grpc_status::{metadata_pairs, GrpcResponse, GrpcStatus, StatusCode};
+use crate::latency::Timing;
+
+// Calls over gRPC-Web and Connect, which are plain HTTP/1.1 requests
+//   gRPC-Web: https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-WEB.md
+//   Connect:  https://connectrpc.com/docs/protocol
+
+type Sender = hyper::client::conn::http1::SendRequest<Full<Bytes>>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Codec {
+    Proto,
+    Json,
+}
+
+// envelope flags
+const COMPRESSED: u8 = 0x01;
+const CONNECT_END_STREAM: u8 = 0x02;
+const GRPC_WEB_TRAILERS: u8 = 0x80;
+
+fn codec_of(protocol: Protocol) -> Codec {
+    if protocol == Protocol::ConnectJson { Codec::Json } else { Codec::Proto }
+}
+
+fn status_of(code: tonic::Code, message: impl Into<String>) -> GrpcStatus {
+    GrpcStatus::from(&tonic::Status::new(code, message))
+}
+
+pub async fn call(target: &Target, path: &str, method: &MethodDescriptor, messages: &[DynamicMessage], metadata: &BTreeMap<String, String>, timeouts: Timeouts, network: &Network) -> GrpcResponse {
+    let mut response = GrpcResponse { status: GrpcStatus::ok(), headers: vec![], trailers: vec![], messages: vec![], timing: Timing::default(), attempts: 1 };
+    let started = Instant::now();
+    let streaming = method.is_client_streaming() || method.is_server_streaming();
+    let exchanged = async {
+        let request = request_of(target, path, streaming, messages, metadata, &timeouts)?;
+        exchange(target, method, request, &timeouts, network, started, &mut response).await
+    };
+    let result = match timeouts.deadline {
+        Some(deadline) => tokio::time::timeout(deadline, exchanged).await
+            .unwrap_or_else(|_| Err(status_of(tonic::Code::DeadlineExceeded, format!("Deadline of {:?} exceeded", deadline)))),
+        None => exchanged.await,
+    };
+    response.timing.total = started.elapsed();
+    if let Err(status) = result {
+        response.status = status;
+    }
+    response
+}
+
+async fn exchange(
+    target: &Target,
+    method: &MethodDescriptor,
+    request: http::Request<Full<Bytes>>,
+    timeouts: &Timeouts,
+    network: &Network,
+    started: Instant,
+    response: &mut GrpcResponse,
+) -> Result<(), GrpcStatus> {
+    let streaming = method.is_client_streaming() || method.is_server_streaming();
+    let mut sender = connect(target, timeouts, network).await?;
+    response.timing.connect = started.elapsed();
+    let (parts, body) = sender.send_request(request).await
+        .map_err(|e| status_of(tonic::Code::Unavailable, format!("Failed to send request: {}", e)))?
+        .into_parts();
+    let mut receiver = Receiver { output: method.output(), codec: codec_of(target.protocol), started, last_arrival: None };
+    match target.protocol {
+        Protocol::GrpcWeb | Protocol::GrpcWebText => {
+            response.headers = pairs_of(&parts.headers);
+            if parts.status != http::StatusCode::OK {
+                return Err(status_of_http(parts.status));
+            }
+            read_grpc_web(target.protocol == Protocol::GrpcWebText, &parts.headers, body, &mut receiver, response).await
+        }
+        Protocol::ConnectJson | Protocol::ConnectProto if streaming => {
+            response.headers = pairs_of(&parts.headers);
+            if parts.status != http::StatusCode::OK {
+                return Err(connect_error_of(parts.status, &collect(body).await?));
+            }
+            read_connect_stream(body, &mut receiver, response).await
+        }
+        Protocol::ConnectJson | Protocol::ConnectProto => {
+            // trailers of unary calls come as headers prefixed with `trailer-`
+            let (trailers, headers): (Vec<_>, Vec<_>) = pairs_of(&parts.headers).into_iter()
+                .partition(|(k, _)| k.starts_with("trailer-"));
+            response.headers = headers;
+            response.trailers = trailers.into_iter().map(|(k, v)| (k["trailer-".len()..].to_string(), v)).collect();
+            let bytes = collect(body).await?;
+            if parts.status != http::StatusCode::OK {
+                return Err(connect_error_of(parts.status, &bytes));
+            }
+            receiver.receive(&bytes, response)
+        }
+        Protocol::Grpc => Err(status_of(tonic::Code::Internal, "gRPC is not carried over HTTP/1.1")),
+    }
+}
+
+struct Receiver {
+    output: MessageDescriptor,
+    codec: Codec,
+    started: Instant,
+    last_arrival: Option<Instant>,
+}
+
+impl Receiver {
+    fn receive(&mut self, bytes: &[u8], response: &mut GrpcResponse) -> Result<(), GrpcStatus> {
+        let arrival = Instant::now();
+        match self.last_arrival {
+            Some(previous) => response.timing.inter_arrival.push(arrival - previous),
+            None => response.timing.first_message = Some(arrival - self.started),
+        }
+        self.last_arrival = Some(arrival);
+        let message = decode(self.codec, &self.output, bytes)?;
+        response.messages.push(serde_json::to_value(&message).map_err(|e| status_of(tonic::Code::Internal, e.to_string()))?);
+        Ok(())
+    }
+}
+
+fn encode(codec: Codec, message: &DynamicMessage) -> Result<Vec<u8>, GrpcStatus> {
+    match codec {
+        Codec::Proto => Ok(message.encode_to_vec()),
+        Codec::Json => serde_json::to_vec(message).map_err(|e| status_of(tonic::Code::Internal, e.to_string())),
+    }
+}
+
+fn decode(codec: Codec, output: &MessageDescriptor, bytes: &[u8]) -> Result<DynamicMessage, GrpcStatus> {
+    let decoded = match codec {
+        Codec::Proto => DynamicMessage::decode(output.clone(), bytes).map_err(|e| e.to_string()),
+        Codec::Json => DynamicMessage::deserialize(output.clone(), &mut serde_json::Deserializer::from_slice(bytes)).map_err(|e| e.to_string()),
+    };
+    decoded.map_err(|e| status_of(tonic::Code::Internal, format!("Failed to decode `{}`: {}", output.full_name(), e)))
+}
+
+fn envelope(flags: u8, message: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(message.len() + 5);
+    framed.push(flags);
+    framed.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    framed.extend_from_slice(message);
+    framed
+}
+
+// flags and payload of the next complete envelope in `buffer`
+fn next_envelope(buffer: &mut BytesMut) -> Option<(u8, Bytes)> {
+    if buffer.len() < 5 {
+        return None;
+    }
+    let length = u32::from_be_bytes([buffer[1], buffer[2], buffer[3], buffer[4]]) as usize;
+    if buffer.len() < 5 + length {
+        return None;
+    }
+    let flags = buffer[0];
+    buffer.advance(5);
+    Some((flags, buffer.split_to(length).freeze()))
+}
+
+fn request_of(
+    target: &Target,
+    path: &str,
+    streaming: bool,
+    messages: &[DynamicMessage],
+    metadata: &BTreeMap<String, String>,
+    timeouts: &Timeouts,
+) -> Result<http::Request<Full<Bytes>>, GrpcStatus> {
+    let codec = codec_of(target.protocol);
+    let encoded = messages.iter().map(|m| encode(codec, m)).collect::<Result<Vec<Vec<u8>>, GrpcStatus>>()?;
+    let enveloped = || encoded.iter().flat_map(|m| envelope(0, m)).collect::<Vec<u8>>();
+    let (content_type, body) = match target.protocol {
+        Protocol::GrpcWeb => ("application/grpc-web+proto", enveloped()),
+        Protocol::GrpcWebText => ("application/grpc-web-text+proto", STANDARD.encode(enveloped()).into_bytes()),
+        Protocol::ConnectJson if streaming => ("application/connect+json", enveloped()),
+        Protocol::ConnectProto if streaming => ("application/connect+proto", enveloped()),
+        Protocol::ConnectJson | Protocol::ConnectProto => {
+            let [message] = encoded.as_slice() else {
+                return Err(status_of(tonic::Code::InvalidArgument, format!("A unary call takes a single message, not {}", encoded.len())));
+            };
+            (if codec == Codec::Json { "application/json" } else { "application/proto" }, message.clone())
+        }
+        Protocol::Grpc => return Err(status_of(tonic::Code::Internal, "gRPC is not carried over HTTP/1.1")),
+    };
+    let mut request = http::Request::post(path)
+        .header(http::header::HOST, authority_of(target))
+        .header(http::header::CONTENT_TYPE, content_type)
+        .header(http::header::USER_AGENT, concat!("zrpc-cli/", env!("CARGO_PKG_VERSION")));
+    let deadline = timeouts.deadline.map(|d| d.as_millis().max(1));
+    match target.protocol {
+        Protocol::GrpcWeb | Protocol::GrpcWebText => {
+            request = request.header(http::header::ACCEPT, content_type).header("x-grpc-web", "1");
+            if let Some(ms) = deadline {
+                request = request.header("grpc-timeout", format!("{}m", ms));
+            }
+        }
+        _ => {
+            request = request.header("connect-protocol-version", "1");
+            if let Some(ms) = deadline {
+                request = request.header("connect-timeout-ms", ms.to_string());
+            }
+        }
+    }
+    let mut request = request.body(Full::new(Bytes::from(body)))
+        .map_err(|e| status_of(tonic::Code::InvalidArgument, format!("Invalid request: {}", e)))?;
+    let mut added = MetadataMap::new();
+    grpc_client::add_metadata(&mut added, metadata).map_err(|e| GrpcStatus::from(&e))?;
+    request.headers_mut().extend(added.into_headers());
+    Ok(request)
+}
+
+fn authority_of(target: &Target) -> String {
+    let port = target.port.as_ref().map_or(if target.tls { 443 } else { 80 }, |p| p.0);
+    match &target.host {
+        Host::Dns { name, .. } => format!("{}:{}", name, port),
+        host => format!("{}:{}", host, port),
+    }
+}
+
+async fn connect(target: &Target, timeouts: &Timeouts, network: &Network) -> Result<Sender, GrpcStatus> {
+    let port = target.port.as_ref().map_or(if target.tls { 443 } else { 80 }, |p| p.0);
+    let host = match &target.host {
+        Host::Name(name) | Host::Dns { name } => name.clone(),
+        Host::Ipv6(address) => address.to_string(),
+        host => return Err(status_of(tonic::Code::InvalidArgument, format!("{} can't dial `{}`", target.protocol, host))),
+    };
+    let tunnel = proxy::tunnel_for(&network.proxies, &target.host, port).map_err(|e| status_of(tonic::Code::InvalidArgument, e))?;
+    let stream = match tunnel {
+        Some(tunnel) => tunnel.open(timeouts.connect).await
+            .map_err(|e| status_of(tonic::Code::Unavailable, format!("Failed to connect to `{}`: {}", target, e)))?,
+        None => tokio::time::timeout(timeouts.connect, tokio::net::TcpStream::connect((host.as_str(), port))).await
+            .map_err(|_| status_of(tonic::Code::Unavailable, format!("Connecting to `{}` timed out", target)))?
+            .map_err(|e| status_of(tonic::Code::Unavailable, format!("Failed to connect to `{}`: {}", target, e)))?,
+    };
+    if !target.tls {
+        return handshake(stream).await;
+    }
+    let server_name = rustls::pki_types::ServerName::try_from(host.clone())
+        .map_err(|e| status_of(tonic::Code::InvalidArgument, format!("Invalid server name `{}`: {}", host, e)))?;
+    let stream = tokio_rustls::TlsConnector::from(tls_config()?).connect(server_name, stream).await
+        .map_err(|e| status_of(tonic::Code::Unavailable, format!("TLS handshake with `{}` failed: {}", target, e)))?;
+    handshake(stream).await
+}
+
+fn tls_config() -> Result<Arc<rustls::ClientConfig>, GrpcStatus> {
+    let mut roots = rustls::RootCertStore::empty();
+    let (added, _) = roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+    debug!("Loaded {} root certificates", added);
+    let mut config = rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| status_of(tonic::Code::Internal, format!("Invalid TLS config: {}", e)))?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+async fn handshake<S>(stream: S) -> Result<Sender, GrpcStatus>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await
+        .map_err(|e| status_of(tonic::Code::Unavailable, format!("HTTP handshake failed: {}", e)))?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            debug!("Connection closed: {}", e);
+        }
+    });
+    Ok(sender)
+}
+
+async fn collect(body: Incoming) -> Result<Bytes, GrpcStatus> {
+    body.collect().await
+        .map(|collected| collected.to_bytes())
+        .map_err(|e| status_of(tonic::Code::Unavailable, format!("Failed to read response: {}", e)))
+}
+
+async fn read_grpc_web(text: bool, headers: &HeaderMap, mut body: Incoming, receiver: &mut Receiver, response: &mut GrpcResponse) -> Result<(), GrpcStatus> {
+    let (mut buffer, mut encoded) = (BytesMut::new(), Vec::new());
+    let mut trailers: Option<HeaderMap> = None;
+    while let Some(frame) = body.frame().await {
+        let frame = frame.map_err(|e| status_of(tonic::Code::Unavailable, format!("Failed to read response: {}", e)))?;
+        let Ok(data) = frame.into_data() else { continue };
+        if text {
+            // base64 decodes by 4 characters, and each response chunk may carry its own padding
+            encoded.extend(data.iter().filter(|b| !b.is_ascii_whitespace()));
+            let complete = encoded.len() / 4 * 4;
+            for quad in encoded[..complete].chunks(4) {
+                buffer.extend_from_slice(&STANDARD.decode(quad).map_err(|e| status_of(tonic::Code::Internal, format!("Invalid grpc-web-text response: {}", e)))?);
+            }
+            encoded.drain(..complete);
+        } else {
+            buffer.extend_from_slice(&data);
+        }
+        while let Some((flags, payload)) = next_envelope(&mut buffer) {
+            if flags & GRPC_WEB_TRAILERS != 0 {
+                trailers = Some(trailers_of(&payload));
+            } else if flags & COMPRESSED != 0 {
+                return Err(status_of(tonic::Code::Unimplemented, "Compressed responses are not supported"));
+            } else {
+                receiver.receive(&payload, response)?;
+            }
+        }
+    }
+    // a response without messages may carry the status in its headers
+    let trailers = trailers.unwrap_or_else(|| headers.clone());
+    response.trailers = pairs_of(&trailers);
+    status_from_headers(&trailers)
+}
+
+// `key: value` lines of a trailers frame
+fn trailers_of(payload: &[u8]) -> HeaderMap {
+    let mut trailers = HeaderMap::new();
+    for line in String::from_utf8_lossy(payload).split("\r\n") {
+        let Some((key, value)) = line.split_once(':') else { continue };
+        if let (Ok(key), Ok(value)) = (HeaderName::from_bytes(key.trim().to_lowercase().as_bytes()), HeaderValue::from_str(value.trim())) {
+            trailers.append(key, value);
+        }
+    }
+    trailers
+}
+
+fn status_from_headers(headers: &HeaderMap) -> Result<(), GrpcStatus> {
+    let mut headers = headers.clone();
+    // tonic doesn't tolerate broken details
+    let broken_details = headers.get("grpc-status-details-bin").is_some_and(|d| STANDARD.decode(d.as_bytes()).is_err());
+    if broken_details {
+        headers.remove("grpc-status-details-bin");
+    }
+    match tonic::Status::from_header_map(&headers) {
+        Some(status) if status.code() == tonic::Code::Ok => Ok(()),
+        Some(status) => Err(GrpcStatus::from(&status)),
+        None => Err(status_of(tonic::Code::Internal, "The response has no grpc-status")),
+    }
+}
+
+async fn read_connect_stream(mut body: Incoming, receiver: &mut Receiver, response: &mut GrpcResponse) -> Result<(), GrpcStatus> {
+    let mut buffer = BytesMut::new();
+    while let Some(frame) = body.frame().await {
+        let frame = frame.map_err(|e| status_of(tonic::Code::Unavailable, format!("Failed to read response: {}", e)))?;
+        let Ok(data) = frame.into_data() else { continue };
+        buffer.extend_from_slice(&data);
+        while let Some((flags, payload)) = next_envelope(&mut buffer) {
+            if flags & CONNECT_END_STREAM != 0 {
+                return end_of_stream(&payload, response);
+            } else if flags & COMPRESSED != 0 {
+                return Err(status_of(tonic::Code::Unimplemented, "Compressed responses are not supported"));
+            } else {
+                receiver.receive(&payload, response)?;
+            }
+        }
+    }
+    Err(status_of(tonic::Code::Internal, "The stream ended without an end-of-stream message"))
+}
+
+// `{"error": {...}, "metadata": {"key": ["value"]}}`
+fn end_of_stream(payload: &[u8], response: &mut GrpcResponse) -> Result<(), GrpcStatus> {
+    let end: Value = serde_json::from_slice(payload).map_err(|e| status_of(tonic::Code::Internal, format!("Invalid end-of-stream message: {}", e)))?;
+    response.trailers = end["metadata"].as_object().into_iter().flatten()
+        .flat_map(|(k, values)| values.as_array().into_iter().flatten().filter_map(|v| Some((k.clone(), v.as_str()?.to_string()))))
+        .collect();
+    match end.get("error") {
+        Some(error) => Err(connect_status_of(error).unwrap_or_else(|| status_of(tonic::Code::Unknown, error.to_string()))),
+        None => Ok(()),
+    }
+}
+
+// the JSON error of Connect, or the code that goes with the HTTP status
+fn connect_error_of(status: http::StatusCode, body: &[u8]) -> GrpcStatus {
+    serde_json::from_slice::<Value>(body).ok()
+        .and_then(|error| connect_status_of(&error))
+        .unwrap_or_else(|| status_of_http(status))
+}
+
+// `{"code": "not_found", "message": "...", "details": [{"type": "google.rpc.RetryInfo", "value": "<base64>"}]}`
+fn connect_status_of(error: &Value) -> Option<GrpcStatus> {
+    let code = StatusCode::from_name(&error["code"].as_str()?.to_uppercase()).ok()?;
+    let lenient = GeneralPurpose::new(&base64::alphabet::STANDARD, GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent));
+    let details = error["details"].as_array().into_iter().flatten()
+        .filter_map(|d| Some(prost_types::Any {
+            type_url: format!("type.googleapis.com/{}", d["type"].as_str()?),
+            value: lenient.decode(d["value"].as_str()?).ok()?,
+        }))
+        .collect::<Vec<prost_types::Any>>();
+    Some(GrpcStatus::with_details(code, error["message"].as_str().unwrap_or_default().to_string(), &details))
+}
+
+// as gRPC maps HTTP statuses. https://github.com/grpc/grpc/blob/master/doc/http-grpc-status-mapping.md
+fn status_of_http(status: http::StatusCode) -> GrpcStatus {
+    let code = match status.as_u16() {
+        400 => tonic::Code::Internal,
+        401 => tonic::Code::Unauthenticated,
+        403 => tonic::Code::PermissionDenied,
+        404 => tonic::Code::Unimplemented,
+        429 | 502..=504 => tonic::Code::Unavailable,
+        _ => tonic::Code::Unknown,
+    };
+    status_of(code, format!("HTTP status {}", status))
+}
+
+fn pairs_of(headers: &HeaderMap) -> Vec<(String, String)> {
+    metadata_pairs(&MetadataMap::from_headers(headers.clone()))
+}
+
+// Descriptors through reflection over gRPC-Web. Each reflection request is a call of its own
+pub fn fetch_protoset(target: &Target, timeouts: &Timeouts, network: &Network) -> Result<Vec<u8>, String> {
+    grpc_client::runtime().block_on(async {
+        let v1 = Reflection::of(tonic_reflection::pb::v1::FILE_DESCRIPTOR_SET, "grpc.reflection.v1.ServerReflection")?;
+        let (reflection, listed) = match v1.ask(target, timeouts, network, json!({"listServices": ""})).await {
+            Ok(listed) => (v1, listed),
+            Err((StatusCode::Unimplemented, _)) => {
+                let v1alpha = Reflection::of(tonic_reflection::pb::v1alpha::FILE_DESCRIPTOR_SET, "grpc.reflection.v1alpha.ServerReflection")?;
+                let listed = v1alpha.ask(target, timeouts, network, json!({"listServices": ""})).await.map_err(|(_, e)| e)?;
+                (v1alpha, listed)
+            }
+            Err((_, e)) => return Err(e),
+        };
+        let services = listed["listServicesResponse"]["service"].as_array().into_iter().flatten()
+            .filter_map(|s| s["name"].as_str())
+            .filter(|name| !name.starts_with("grpc.reflection."))
+            .map(String::from)
+            .collect::<Vec<String>>();
+
+        let mut files: BTreeMap<String, FileDescriptorProto> = BTreeMap::new();
+        let mut requests: VecDeque<Value> = services.iter().map(|s| json!({"fileContainingSymbol": s})).collect();
+        while let Some(request) = requests.pop_front() {
+            let reply = reflection.ask(target, timeouts, network, request).await.map_err(|(_, e)| e)?;
+            for encoded in reply["fileDescriptorResponse"]["fileDescriptorProto"].as_array().into_iter().flatten() {
+                let bytes = STANDARD.decode(encoded.as_str().unwrap_or_default()).map_err(|e| format!("Invalid descriptor: {}", e))?;
+                let file = FileDescriptorProto::decode(bytes.as_slice()).map_err(|e| format!("Invalid descriptor: {}", e))?;
+                files.insert(file.name().to_string(), file);
+            }
+            // dependencies the server didn't send along
+            let missing = files.values().flat_map(|f| f.dependency.iter())
+                .filter(|d| !files.contains_key(*d))
+                .cloned()
+                .collect::<HashSet<String>>();
+            for dependency in missing {
+                let request = json!({"fileByFilename": dependency});
+                if !requests.contains(&request) {
+                    requests.push_back(request);
+                }
+            }
+        }
+        Ok(FileDescriptorSet { file: ordered(files) }.encode_to_vec())
+    })
+}
+
+struct Reflection {
+    method: MethodDescriptor,
+}
+
+impl Reflection {
+    fn of(file_descriptor_set: &[u8], service: &str) -> Result<Reflection, String> {
+        let pool = DescriptorPool::decode(file_descriptor_set).map_err(|e| format!("Invalid reflection descriptors: {}", e))?;
+        let method = pool.get_service_by_name(service).and_then(|s| s.methods().next())
+            .ok_or_else(|| format!("`{}` not found", service))?;
+        Ok(Reflection { method })
+    }
+
+    async fn ask(&self, target: &Target, timeouts: &Timeouts, network: &Network, request: Value) -> Result<Value, (StatusCode, String)> {
+        let message = DynamicMessage::deserialize(self.method.input(), request).map_err(|e| (StatusCode::Internal, e.to_string()))?;
+        let path = format!("/{}/{}", self.method.parent_service().full_name(), self.method.name());
+        let response = call(target, &path, &self.method, &[message], &BTreeMap::new(), *timeouts, network).await;
+        if !response.status.is_ok() {
+            return Err((response.status.code, format!("Failed to fetch descriptors from `{}`: {}", target, response.status)));
+        }
+        let reply = response.messages.into_iter().next()
+            .ok_or_else(|| (StatusCode::Internal, format!("No reflection response from `{}`", target)))?;
+        match reply["errorResponse"]["errorMessage"].as_str() {
+            Some(error) => Err((StatusCode::Internal, format!("Failed to fetch descriptors from `{}`: {}", target, error))),
+            None => Ok(reply),
+        }
+    }
+}
+
+// dependencies before the files importing them
+fn ordered(files: BTreeMap<String, FileDescriptorProto>) -> Vec<FileDescriptorProto> {
+    fn visit(name: &str, files: &BTreeMap<String, FileDescriptorProto>, visited: &mut HashSet<String>, ordered: &mut Vec<FileDescriptorProto>) {
+        if !visited.insert(name.to_string()) {
+            return;
+        }
+        if let Some(file) = files.get(name) {
+            file.dependency.iter().for_each(|d| visit(d, files, visited, ordered));
+            ordered.push(file.clone());
+        }
+    }
+    let (mut visited, mut ordered) = (HashSet::new(), vec![]);
+    files.keys().for_each(|name| visit(name, &files, &mut visited, &mut ordered));
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use crate::grpc_request_dsl::Port;
+
+    #[test]
+    fn frames_split_across_chunks() {
+        let mut buffer = BytesMut::new();
+        let framed = [envelope(0, b"ab"), envelope(GRPC_WEB_TRAILERS, b"grpc-status: 0\r\n")].concat();
+        buffer.extend_from_slice(&framed[..4]);
+        assert_eq!(next_envelope(&mut buffer), None);
+        buffer.extend_from_slice(&framed[4..]);
+        assert_eq!(next_envelope(&mut buffer), Some((0, Bytes::from_static(b"ab"))));
+        let (flags, payload) = next_envelope(&mut buffer).unwrap();
+        assert_eq!(flags, GRPC_WEB_TRAILERS);
+        assert!(status_from_headers(&trailers_of(&payload)).is_ok());
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn status_from_grpc_web_trailers() {
+        let trailers = trailers_of(b"grpc-status: 5\r\ngrpc-message: no%20such%20user\r\n");
+        let status = status_from_headers(&trailers).unwrap_err();
+        assert_eq!((status.code, status.message.as_str()), (StatusCode::NotFound, "no such user"));
+    }
+
+    #[test]
+    fn status_from_connect_errors() {
+        let status = connect_error_of(http::StatusCode::NOT_FOUND, br#"{"code": "not_found", "message": "no such user"}"#);
+        assert_eq!((status.code, status.message.as_str()), (StatusCode::NotFound, "no such user"));
+        assert_eq!(connect_error_of(http::StatusCode::SERVICE_UNAVAILABLE, b"<html>").code, StatusCode::Unavailable);
+    }
+
+    #[test]
+    fn dependencies_come_first() {
+        let file = |name: &str, dependency: &[&str]| FileDescriptorProto {
+            name: Some(String::from(name)),
+            dependency: dependency.iter().map(|d| d.to_string()).collect(),
+            ..Default::default()
+        };
+        let files = BTreeMap::from([
+            (String::from("a.proto"), file("a.proto", &["b.proto"])),
+            (String::from("b.proto"), file("b.proto", &["c.proto"])),
+            (String::from("c.proto"), file("c.proto", &[])),
+        ]);
+        let names = ordered(files).iter().map(|f| f.name().to_string()).collect::<Vec<String>>();
+        assert_eq!(names, vec!["c.proto", "b.proto", "a.proto"]);
+    }
+
+    fn echo_method() -> MethodDescriptor {
+        use prost_types::field_descriptor_proto::{Label, Type};
+        use prost_types::{DescriptorProto, FieldDescriptorProto, MethodDescriptorProto, ServiceDescriptorProto};
+        let file = FileDescriptorProto {
+            name: Some(String::from("echo.proto")),
+            package: Some(String::from("echo")),
+            syntax: Some(String::from("proto3")),
+            message_type: vec![DescriptorProto {
+                name: Some(String::from("Text")),
+                field: vec![FieldDescriptorProto {
+                    name: Some(String::from("text")),
+                    number: Some(1),
+                    label: Some(Label::Optional as i32),
+                    r#type: Some(Type::String as i32),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            service: vec![ServiceDescriptorProto {
+                name: Some(String::from("Echo")),
+                method: vec![MethodDescriptorProto {
+                    name: Some(String::from("Say")),
+                    input_type: Some(String::from(".echo.Text")),
+                    output_type: Some(String::from(".echo.Text")),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let pool = DescriptorPool::from_file_descriptor_set(FileDescriptorSet { file: vec![file] }).unwrap();
+        pool.get_service_by_name("echo.Echo").and_then(|s| s.methods().next()).unwrap()
+    }
+
+    // answers gRPC-Web and Connect unary calls of `echo.Echo/Say` with the request message and its `x-user`, other paths with not found
+    async fn stand_in(request: http::Request<Incoming>) -> Result<http::Response<Full<Bytes>>, Infallible> {
+        let header = |name: &str| request.headers().get(name).and_then(|v| v.to_str().ok()).unwrap_or_default().to_string();
+        let (content_type, user) = (header("content-type"), header("x-user"));
+        let (grpc_web, connect) = (header("x-grpc-web") == "1", header("connect-protocol-version") == "1");
+        let found = request.uri().path() == "/echo.Echo/Say";
+        let body = request.into_body().collect().await.unwrap().to_bytes();
+        let response = http::Response::builder().header("x-user", user);
+        let response = match content_type.as_str() {
+            "application/grpc-web+proto" if grpc_web && found => response
+                .header("content-type", "application/grpc-web+proto")
+                .body(Full::new(Bytes::from([envelope(0, &body[5..]), envelope(GRPC_WEB_TRAILERS, b"grpc-status: 0\r\nx-served: grpc-web\r\n")].concat()))),
+            "application/json" if connect && found => response
+                .header("content-type", "application/json")
+                .header("trailer-x-served", "connect")
+                .body(Full::new(body)),
+            "application/json" if connect => response
+                .status(http::StatusCode::NOT_FOUND)
+                .header("content-type", "application/json")
+                .body(Full::new(Bytes::from_static(br#"{"code": "not_found", "message": "no such method"}"#))),
+            _ => response.status(http::StatusCode::UNSUPPORTED_MEDIA_TYPE).body(Full::new(Bytes::new())),
+        };
+        Ok(response.unwrap())
+    }
+
+    #[test]
+    fn calls_over_http1() {
+        grpc_client::runtime().block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(hyper::server::conn::http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), hyper::service::service_fn(stand_in)));
+                }
+            });
+            let target = |protocol| Target { host: Host::Name(String::from("127.0.0.1")), port: Some(Port(port)), tls: false, protocol };
+            let method = echo_method();
+            let message = DynamicMessage::deserialize(method.input(), json!({"text": "hi"})).unwrap();
+            let metadata = BTreeMap::from([(String::from("x-user"), String::from("alice"))]);
+            let pair = |k: &str, v: &str| (String::from(k), String::from(v));
+
+            for (protocol, served) in [(Protocol::GrpcWeb, "grpc-web"), (Protocol::ConnectJson, "connect")] {
+                let response = call(&target(protocol), "/echo.Echo/Say", &method, std::slice::from_ref(&message), &metadata, Timeouts::default(), &Network::default()).await;
+                assert!(response.status.is_ok(), "{}: {:?}", protocol, response.status);
+                assert_eq!(response.messages, vec![json!({"text": "hi"})]);
+                assert!(response.headers.contains(&pair("x-user", "alice")), "{}: {:?}", protocol, response.headers);
+                assert!(response.trailers.contains(&pair("x-served", served)), "{}: {:?}", protocol, response.trailers);
+            }
+
+            let missing = call(&target(Protocol::ConnectJson), "/echo.Echo/Missing", &method, &[message], &metadata, Timeouts::default(), &Network::default()).await;
+            assert_eq!((missing.status.code, missing.status.message.as_str()), (StatusCode::NotFound, "no such method"));
+        });
+    }
+}